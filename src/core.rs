@@ -1,20 +1,39 @@
-use std::{cell::RefCell, path::PathBuf, process::exit, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    env,
+    path::PathBuf,
+    process::exit,
+    rc::Rc,
+};
 
 use threadpool::ThreadPool;
 
 use crate::{
     diagnostics::DiagnosticBag,
-    drivers::fs_manager::FilesystemManager,
-    execution::PlanExecutor,
+    drivers::{
+        build_database::BuildDatabase,
+        fs_manager::FilesystemManager,
+        object_cache::ObjectCache,
+        pkg_config::PackageFlags,
+        vendor::{Vendor, VendoredSource, LOCK_FILE_PATH, VENDOR_DIR},
+    },
+    errors::{FetchError, ParsingError, QueryError},
+    execution::{plan_printer::PlanPrinter, PlanExecutor},
     logger::Logger,
     meta::{HELP_MSG, SHORT_HELP, VERSION_MSG},
     parsing::{
         arg_parser::{ArgParser, Args},
-        config::{Config, CONFIG_FILE_PATH},
+        config::{Config, DependencyConfig, WorkspaceConfig, CONFIG_FILE_PATH},
         config_parser::ConfigParser,
     },
     planning::{
-        args_specification::{ArgsSpec, PrintHelp, PrintVersion},
+        args_specification::{
+            ArgsSpec, CompDb, DryRun, FullBuild, FullRun, IncrementalBuild, IncrementalRun,
+            Install, MemberCommand, Package, PrintHelp, PrintVersion, RunTest,
+        },
+        build_target::BuildTarget,
+        exports::Exports,
         plan::Plan,
         planner::Planner,
     },
@@ -22,6 +41,8 @@ use crate::{
 
 pub type DiagnosticsCell = Rc<RefCell<DiagnosticBag>>;
 pub type FilesystemManagerCell = Rc<FilesystemManager>;
+/// Exports of dependencies by canonical directory, None while dependency is being built.
+pub type DependenciesCell = Rc<RefCell<HashMap<PathBuf, Option<Exports>>>>;
 
 #[derive(Default)]
 pub struct Context {
@@ -29,6 +50,19 @@ pub struct Context {
     pub args: Args,
     pub plan: Plan,
     pub thread_pool: ThreadPool,
+    pub build_db: BuildDatabase,
+    /// Output of 'clang++ --version', part of every command fingerprint.
+    pub compiler_version: String,
+    /// llvm-ar or ar, detected only if some static library is built.
+    pub archiver: String,
+    /// Targets being built, by name.
+    pub targets: HashMap<String, BuildTarget>,
+    /// Include directories and libraries of projects this one uses.
+    pub imports: Exports,
+    /// Fetched sources of git and tarball dependencies, compiled into static libraries.
+    pub vendored: Vec<VendoredSource>,
+    /// Flags of 'pkg_config' packages, queried only if something is built.
+    pub packages: PackageFlags,
 }
 
 #[derive(Default)]
@@ -36,6 +70,7 @@ pub struct Core {
     ctx: Context,
     diagnostics: DiagnosticsCell,
     fs_m: FilesystemManagerCell,
+    dependencies: DependenciesCell,
 }
 
 impl Core {
@@ -65,18 +100,290 @@ impl Core {
     }
 
     pub fn make_plan(&mut self) {
-        let mut planner = Planner::new(&mut self.ctx, self.fs_m.clone());
+        let mut planner = Planner::new(&mut self.ctx, self.fs_m.clone(), self.diagnostics.clone());
 
         if let Err(err) = planner.try_make_plan() {
             self.diagnostics.borrow_mut().report_error(err);
         }
     }
 
+    /// With --dry-run plan is only printed.
     pub fn execute_plan(&mut self) {
+        if DryRun.is_satisfied_by(&self.ctx.args) {
+            PlanPrinter::new(&self.ctx).print();
+            return;
+        }
         let executor = PlanExecutor::new(&self.ctx, self.fs_m.clone(), self.diagnostics.clone());
         executor.execute_and_report();
     }
 
+    /// Root of workspace forwards build, run, test and clean to its members.
+    pub fn is_workspace(&self) -> bool {
+        self.ctx.config.workspace.is_some() && MemberCommand.is_satisfied_by(&self.ctx.args)
+    }
+
+    /// Members selected with --member (all by default) and their dependencies are planned
+    /// and executed from their own directories, every member after members it uses.
+    /// Members share thread pool, exports of dependencies are imported by dependents.
+    /// Returns exports of every member which was run.
+    pub fn run_workspace(&mut self) -> HashMap<String, Exports> {
+        let mut exports: HashMap<String, Exports> = HashMap::default();
+        let members = match self.ctx.config.members_in_order() {
+            Ok(members) => members,
+            Err(err) => {
+                self.diagnostics.borrow_mut().report_error(err);
+                return exports;
+            }
+        };
+        let workspace = self.ctx.config.workspace.clone().unwrap_or_default();
+        let needed = match self.ctx.args.named_params.get("member") {
+            Some(name) if !members.iter().any(|(m, _)| m == name) => {
+                let err = QueryError::InvalidMember(name.clone());
+                self.diagnostics.borrow_mut().report_error(err);
+                return exports;
+            }
+            Some(name) => Self::members_used_by(&workspace, &members, name),
+            None => members.iter().map(|(m, _)| m.clone()).collect(),
+        };
+        let root = match env::current_dir() {
+            Ok(root) => root,
+            Err(err) => {
+                self.diagnostics.borrow_mut().report_error(err);
+                return exports;
+            }
+        };
+
+        for (name, dir) in members.iter().filter(|(m, _)| needed.contains(m)) {
+            let dir = root.join(dir);
+            if let Err(err) = env::set_current_dir(&dir) {
+                self.diagnostics.borrow_mut().report_error(err);
+                return exports;
+            }
+            Logger::info(&format!("Member {name}"));
+
+            let mut member = self.nested(self.ctx.args.clone());
+            for dep in workspace.dependencies_of(name) {
+                if let Some(dep_exports) = exports.get(dep) {
+                    member.ctx.imports.extend(dep_exports);
+                }
+            }
+            let used = workspace
+                .dependencies
+                .values()
+                .any(|deps| deps.contains(name));
+            member.run_member(used);
+            exports.insert(name.clone(), Exports::of(&member.ctx, &dir));
+
+            if let Err(err) = env::set_current_dir(&root) {
+                self.diagnostics.borrow_mut().report_error(err);
+                return exports;
+            }
+            if self.diagnostics.borrow().contains_error() {
+                return exports;
+            }
+        }
+        exports
+    }
+
+    /// Whole pipeline of workspace member or dependency, project used by others is only
+    /// built (with preset of dependent) and without '[[targets]]' as static library.
+    fn run_member(&mut self, used: bool) {
+        self.parse_config();
+        if self.diagnostics.borrow().contains_error() {
+            return;
+        }
+        if used
+            && IncrementalRun
+                .or(FullRun)
+                .or(RunTest)
+                .is_satisfied_by(&self.ctx.args)
+        {
+            self.ctx.args = self.dependency_args();
+        }
+        if used && self.ctx.config.targets.is_empty() {
+            self.ctx.args.flags.insert("lib".into());
+        }
+        self.build_dependencies();
+        if self.diagnostics.borrow().contains_error() {
+            return;
+        }
+
+        self.make_plan();
+        if !self.diagnostics.borrow().contains_error() {
+            self.execute_plan();
+        }
+    }
+
+    /// Dependencies are built with preset of dependent before it is planned (every one once),
+    /// then include directories of everything imported are added to 'include_dirs'.
+    pub fn build_dependencies(&mut self) {
+        if IncrementalBuild
+            .or(FullBuild)
+            .or(IncrementalRun)
+            .or(FullRun)
+            .or(RunTest)
+            .or(Install)
+            .or(Package)
+            .or(CompDb)
+            .is_satisfied_by(&self.ctx.args)
+        {
+            let dependencies = self.ctx.config.dependencies.clone();
+            let local = dependencies.iter().filter(|(_, d)| d.path.is_some());
+            for (name, dependency) in local {
+                match self.build_dependency(name, dependency) {
+                    Ok(exports) => self.ctx.imports.extend(&exports),
+                    Err(err) => return self.diagnostics.borrow_mut().report_error(err),
+                }
+                if self.diagnostics.borrow().contains_error() {
+                    return;
+                }
+            }
+            if let Err(err) = self.fetch_vendored() {
+                return self.diagnostics.borrow_mut().report_error(err);
+            }
+        }
+        let imported = self.ctx.imports.include_dirs.iter().cloned();
+        self.ctx.config.include_dirs.extend(imported);
+        let defines: Vec<String> = self
+            .ctx
+            .imports
+            .defines
+            .iter()
+            .map(|d| format!("-D{d}"))
+            .collect();
+        for preset in self.ctx.config.presets.values_mut() {
+            preset.cflags.extend_from_slice(&defines);
+        }
+    }
+
+    fn build_dependency(
+        &mut self,
+        name: &str,
+        dependency: &DependencyConfig,
+    ) -> Result<Exports, ParsingError> {
+        let Some(path) = &dependency.path else {
+            return Err(ParsingError::InvalidDependency(name.into()));
+        };
+        if !path.join(CONFIG_FILE_PATH).exists() {
+            return Err(ParsingError::MissingDependency(name.into(), path.clone()));
+        }
+        let dir = path.canonicalize()?;
+        if let Some(built) = self.dependencies.borrow().get(&dir) {
+            return built
+                .clone()
+                .ok_or_else(|| ParsingError::DependencyCycle(name.into()));
+        }
+        self.dependencies.borrow_mut().insert(dir.clone(), None);
+
+        let root = env::current_dir()?;
+        env::set_current_dir(&dir)?;
+        Logger::info(&format!("Dependency {name}"));
+
+        let mut nested = self.nested(self.dependency_args());
+        nested.run_member(true);
+        let exports = Exports::of(&nested.ctx, &dir);
+
+        env::set_current_dir(root)?;
+        self.dependencies
+            .borrow_mut()
+            .insert(dir, Some(exports.clone()));
+        Ok(exports)
+    }
+
+    /// Git and tarball dependencies are fetched into cache (once) and verified against Cum.lock,
+    /// their sources are compiled along with project. With --dry-run nothing is fetched and
+    /// lock file isn't written.
+    fn fetch_vendored(&mut self) -> Result<(), FetchError> {
+        let dependencies = self.ctx.config.dependencies.clone();
+        let fetched: Vec<_> = dependencies
+            .iter()
+            .filter(|(_, d)| d.path.is_none())
+            .collect();
+        let lock_path = PathBuf::from(LOCK_FILE_PATH);
+        if fetched.is_empty() && !lock_path.exists() {
+            return Ok(());
+        }
+
+        let dir = ObjectCache::user_dir().ok_or(FetchError::NoCacheDir)?;
+        let dry_run = DryRun.is_satisfied_by(&self.ctx.args);
+        let mut vendor = Vendor::new(dir.join(VENDOR_DIR), &lock_path, dry_run)?;
+        for (name, dependency) in fetched {
+            let source = vendor.fetch(name, dependency)?;
+            self.ctx.imports.extend(&Exports {
+                include_dirs: source.include_dirs.clone(),
+                ..Default::default()
+            });
+            self.ctx.vendored.push(source);
+        }
+        if !dry_run {
+            vendor.save(&lock_path)?;
+        }
+        Ok(())
+    }
+
+    /// Dependencies are only built (or get compilation database), with same preset (test preset for tests, release one
+    /// for install) and force.
+    fn dependency_args(&self) -> Args {
+        let args = &self.ctx.args;
+        // Compilation database of dependency is only written, as it is for dependent.
+        let command = if CompDb.is_satisfied_by(args) {
+            "compdb"
+        } else {
+            "build"
+        };
+        let mut res = Args {
+            command: Some(command.into()),
+            ..Default::default()
+        };
+        for flag in ["f", "force", "dry-run"] {
+            if args.have_flag(flag) {
+                res.flags.insert(flag.into());
+            }
+        }
+        let preset = if RunTest.is_satisfied_by(args) {
+            Some("test".to_string())
+        } else if Install.or(Package).is_satisfied_by(args) {
+            args.named_params
+                .get("preset")
+                .cloned()
+                .or(Some("release".into()))
+        } else {
+            args.named_params.get("preset").cloned()
+        };
+        res.named_params
+            .extend(preset.map(|p| ("preset".to_string(), p)));
+        res
+    }
+
+    /// Core of project in current directory, sharing diagnostics, thread pool and dependencies.
+    fn nested(&self, args: Args) -> Core {
+        Core {
+            ctx: Context {
+                args,
+                thread_pool: self.ctx.thread_pool.clone(),
+                ..Default::default()
+            },
+            diagnostics: self.diagnostics.clone(),
+            fs_m: Rc::new(FilesystemManager::default()),
+            dependencies: self.dependencies.clone(),
+        }
+    }
+
+    /// Member and every member it uses (including indirectly).
+    fn members_used_by(
+        workspace: &WorkspaceConfig,
+        members: &[(String, PathBuf)],
+        name: &str,
+    ) -> HashSet<String> {
+        let mut res = HashSet::from([name.to_string()]);
+        for (member, _) in members.iter().rev() {
+            if res.contains(member) {
+                res.extend(workspace.dependencies_of(member).iter().cloned());
+            }
+        }
+        res
+    }
+
     pub fn verify_diagnostics(&self) {
         let bind = self.diagnostics.borrow();
         if bind.contains_error() {
@@ -104,3 +411,127 @@ impl Core {
         &self.ctx
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use std::{
+        collections::HashSet,
+        env, fs,
+        path::{Path, PathBuf},
+        process,
+    };
+
+    use crate::{parsing::config::WorkspaceConfig, test_utils::set_dir_to_tests};
+
+    use super::Core;
+
+    fn write_member(dir: &Path, name: &str, files: &[&str]) {
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("Cum.toml"), format!("target_name = \"{name}\"\n")).unwrap();
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "int f() { return 0; }\n").unwrap();
+        }
+    }
+
+    #[test]
+    fn members_used_by_follows_dependencies() {
+        let workspace = WorkspaceConfig {
+            dependencies: [
+                ("app".to_string(), vec!["net".to_string()]),
+                ("net".to_string(), vec!["core".to_string()]),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let members: Vec<(String, PathBuf)> = ["core", "net", "tool", "app"]
+            .iter()
+            .map(|m| (m.to_string(), PathBuf::from(m)))
+            .collect();
+
+        let used = Core::members_used_by(&workspace, &members, "app");
+        assert_eq!(
+            used,
+            HashSet::from(["app", "net", "core"].map(String::from))
+        );
+        let used = Core::members_used_by(&workspace, &members, "tool");
+        assert_eq!(used, HashSet::from(["tool".to_string()]));
+    }
+
+    #[test]
+    fn workspace_test_builds_used_members_as_libraries() {
+        let _dir = set_dir_to_tests();
+        let tests_dir = env::current_dir().unwrap();
+        let root = env::temp_dir().join(format!("cum_workspace_test_{}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+        fs::write(
+            root.join("Cum.toml"),
+            "[workspace]\nmembers = [\"core\", \"app\", \"tool\"]\n\n\
+             [workspace.dependencies]\napp = [\"core\"]\n",
+        )
+        .unwrap();
+        // Used member has no test runner, it is only built for tests of dependent.
+        write_member(
+            &root.join("core"),
+            "core",
+            &["src/core.cpp", "include/core.hpp"],
+        );
+        write_member(
+            &root.join("app"),
+            "app",
+            &["src/main.cpp", "tests/test_runner.cpp"],
+        );
+        write_member(&root.join("tool"), "tool", &["src/main.cpp"]);
+
+        env::set_current_dir(&root).unwrap();
+        let mut core = Core::default();
+        core.parse_args(
+            ["cum.exe", "test", "--member=app", "--dry-run"]
+                .map(String::from)
+                .into(),
+        );
+        core.parse_config();
+        let exports = core.run_workspace();
+        env::set_current_dir(tests_dir).unwrap();
+
+        assert!(!core.diagnostics.borrow().contains_error());
+        assert_eq!(exports.len(), 2, "unused member is not run");
+        let core_lib = root.join("core/target/test_runner/libcore.a");
+        assert_eq!(exports["core"].libs, vec![core_lib.clone()]);
+        assert!(exports["app"].libs.contains(&core_lib));
+        assert!(exports["app"]
+            .include_dirs
+            .contains(&root.join("core/include")));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn workspace_forwards_package_to_members() {
+        let _dir = set_dir_to_tests();
+        let tests_dir = env::current_dir().unwrap();
+        let root = env::temp_dir().join(format!("cum_workspace_package_{}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+        fs::write(root.join("Cum.toml"), "[workspace]\nmembers = [\"app\"]\n").unwrap();
+        write_member(&root.join("app"), "app", &["src/main.cpp"]);
+
+        env::set_current_dir(&root).unwrap();
+        let mut core = Core::default();
+        core.parse_args(["cum.exe", "package", "--dry-run"].map(String::from).into());
+        core.parse_config();
+        let is_workspace = core.is_workspace();
+        let exports = core.run_workspace();
+        env::set_current_dir(tests_dir).unwrap();
+
+        assert!(is_workspace);
+        assert!(!core.diagnostics.borrow().contains_error());
+        assert_eq!(exports.len(), 1);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
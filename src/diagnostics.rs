@@ -58,7 +58,7 @@ impl DiagnosticBag {
     }
 
     #[inline]
-    pub fn get_sorted(&self) -> Vec<Diagnostic> {
+    fn get_sorted(&self) -> Vec<Diagnostic> {
         let mut sorted = self.diagnostics.clone();
         sorted.sort_by(|a, _| {
             if a.kind == DiagnosticKind::Warning {
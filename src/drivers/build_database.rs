@@ -0,0 +1,195 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{errors::ExecutionError, logger::Logger};
+
+use super::fs_manager::FilesystemManager;
+
+pub const BUILD_DB_PATH: &str = "target/cum_db.toml";
+
+/// Persistent record of the inputs every object file was built from.
+/// Keys are object pathes relative to root.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct BuildDatabase {
+    #[serde(default)]
+    objects: BTreeMap<String, ObjectRecord>,
+
+    /// Command line fingerprint of every linked output.
+    #[serde(default)]
+    links: BTreeMap<String, String>,
+
+    #[serde(skip)]
+    modified: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectRecord {
+    /// Hash of compiler identity and full command line.
+    #[serde(default)]
+    pub fingerprint: String,
+
+    /// Content hash of every input (source and headers) keyed by path.
+    #[serde(default)]
+    pub inputs: BTreeMap<String, String>,
+}
+
+impl BuildDatabase {
+    /// Missing or corrupted database is treated as empty.
+    pub fn load(path: &Path) -> Self {
+        let Ok(str) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+
+        match toml::from_str(&str) {
+            Ok(db) => db,
+            Err(err) => {
+                Logger::warning(&format!(
+                    "Build database is corrupted and will be recreated: [{}]",
+                    err.message()
+                ));
+                Self::default()
+            }
+        }
+    }
+
+    /// Does nothing if database wasn't modified since loading.
+    pub fn save(&self, path: &Path) -> Result<(), ExecutionError> {
+        if !self.modified {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn get(&self, obj: &Path) -> Option<&ObjectRecord> {
+        self.objects.get(&Self::key(obj))
+    }
+
+    #[inline]
+    pub fn insert(&mut self, obj: &Path, record: ObjectRecord) {
+        self.objects.insert(Self::key(obj), record);
+        self.modified = true;
+    }
+
+    /// Forget removed object, does nothing for other pathes.
+    #[inline]
+    pub fn remove(&mut self, obj: &Path) {
+        if self.objects.remove(&Self::key(obj)).is_some() {
+            self.modified = true;
+        }
+    }
+
+    #[inline]
+    pub fn get_link(&self, output: &Path) -> Option<&String> {
+        self.links.get(&Self::key(output))
+    }
+
+    #[inline]
+    pub fn insert_link(&mut self, output: &Path, fingerprint: String) {
+        self.links.insert(Self::key(output), fingerprint);
+        self.modified = true;
+    }
+
+    /// Command line fingerprint, any change of flags or compiler changes it.
+    pub fn fingerprint(compiler_version: &str, args: &[String]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(compiler_version.as_bytes());
+        for arg in args {
+            hasher.update([0]);
+            hasher.update(arg.as_bytes());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    #[inline]
+    fn key(obj: &Path) -> String {
+        obj.display().to_string()
+    }
+}
+
+impl ObjectRecord {
+    /// Hash every input, None if some of them can't be read.
+    pub fn from_inputs(
+        inputs: &[PathBuf],
+        fingerprint: String,
+        hashes: &mut HashMap<PathBuf, String>,
+    ) -> Option<Self> {
+        let mut res = Self {
+            fingerprint,
+            ..Default::default()
+        };
+        for input in inputs {
+            let hash = match hashes.get(input) {
+                Some(hash) => hash.clone(),
+                None => {
+                    let hash = FilesystemManager::hash_file(input)?;
+                    hashes.insert(input.clone(), hash.clone());
+                    hash
+                }
+            };
+            res.inputs.insert(input.display().to_string(), hash);
+        }
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use super::{BuildDatabase, ObjectRecord};
+    use crate::test_utils::set_dir_to_tests;
+
+    #[test]
+    fn build_db_serialization() {
+        let mut db = BuildDatabase::default();
+        let obj = PathBuf::from("target/obj/debug/src.main.o");
+        let mut record = ObjectRecord::default();
+        record.inputs.insert("src/main.cpp".into(), "hash".into());
+
+        db.insert(&obj, record.clone());
+        assert_eq!(db.get(&obj), Some(&record));
+
+        let str = toml::to_string(&db).unwrap();
+        let loaded: BuildDatabase = toml::from_str(&str).unwrap();
+        assert_eq!(loaded.get(&obj), Some(&record));
+    }
+
+    #[test]
+    fn build_db_record_from_inputs() {
+        let _dir = set_dir_to_tests();
+        let mut hashes = HashMap::default();
+        let inputs: Vec<PathBuf> = vec!["src/main.cpp".into(), "include/file.hpp".into()];
+
+        let record = ObjectRecord::from_inputs(&inputs, "fp".into(), &mut hashes).unwrap();
+        assert_eq!(record.inputs.len(), 2);
+        assert_eq!(record.fingerprint, "fp");
+        assert_eq!(hashes.len(), 2);
+
+        let missing: Vec<PathBuf> = vec!["src/missing.cpp".into()];
+        assert!(ObjectRecord::from_inputs(&missing, "fp".into(), &mut hashes).is_none());
+    }
+
+    #[test]
+    fn build_db_fingerprint_tracks_args() {
+        let args: Vec<String> = vec!["-std=c++20".into(), "-O0".into()];
+        let other: Vec<String> = vec!["-std=c++20".into(), "-O3".into()];
+        let glued: Vec<String> = vec!["-std=c++20-O0".into()];
+
+        let fp = BuildDatabase::fingerprint("clang 18", &args);
+        assert_eq!(fp, BuildDatabase::fingerprint("clang 18", &args));
+        assert_ne!(fp, BuildDatabase::fingerprint("clang 19", &args));
+        assert_ne!(fp, BuildDatabase::fingerprint("clang 18", &other));
+        assert_ne!(fp, BuildDatabase::fingerprint("clang 18", &glued));
+    }
+}
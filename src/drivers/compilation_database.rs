@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+
+use path_clean::PathClean;
+
+/// Read by clangd and clang-tidy from project root.
+pub const COMPDB_PATH: &str = "compile_commands.json";
+
+/// How single source is compiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileCommand {
+    pub file: PathBuf,
+    pub output: PathBuf,
+    /// Compiler followed by its arguments.
+    pub arguments: Vec<String>,
+}
+
+/// JSON compilation database, pathes of commands are relative to 'directory'.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CompilationDatabase {
+    pub directory: PathBuf,
+    pub commands: Vec<CompileCommand>,
+}
+
+impl CompilationDatabase {
+    pub fn to_json(&self) -> String {
+        if self.commands.is_empty() {
+            return "[]\n".into();
+        }
+        let directory = Self::quoted(&self.directory.clean());
+        let entries: Vec<String> = self
+            .commands
+            .iter()
+            .map(|c| {
+                let arguments: Vec<String> =
+                    c.arguments.iter().map(|a| Self::escaped(a)).collect();
+                format!(
+                    "  {{\n    \"directory\": {directory},\n    \"file\": {},\n    \"output\": {},\n    \"arguments\": [{}]\n  }}",
+                    Self::quoted(&c.file.clean()),
+                    Self::quoted(&c.output.clean()),
+                    arguments.join(", ")
+                )
+            })
+            .collect();
+        format!("[\n{}\n]\n", entries.join(",\n"))
+    }
+
+    #[inline]
+    fn quoted(path: &Path) -> String {
+        Self::escaped(&path.display().to_string())
+    }
+
+    /// JSON string literal.
+    fn escaped(str: &str) -> String {
+        let mut res = String::from("\"");
+        for c in str.chars() {
+            match c {
+                '"' => res.push_str("\\\""),
+                '\\' => res.push_str("\\\\"),
+                '\n' => res.push_str("\\n"),
+                '\t' => res.push_str("\\t"),
+                c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+                c => res.push(c),
+            }
+        }
+        res.push('"');
+        res
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{CompilationDatabase, CompileCommand};
+
+    #[test]
+    fn compilation_database_is_escaped_json() {
+        let db = CompilationDatabase {
+            directory: "/home/user/proj".into(),
+            commands: vec![CompileCommand {
+                file: "./src/main.cpp".into(),
+                output: "target/obj/debug/src/main.cpp.o".into(),
+                arguments: vec![
+                    "clang++".into(),
+                    "-DNAME=\"cum\"".into(),
+                    "-c".into(),
+                    "src/main.cpp".into(),
+                ],
+            }],
+        };
+        assert_eq!(
+            db.to_json(),
+            r#"[
+  {
+    "directory": "/home/user/proj",
+    "file": "src/main.cpp",
+    "output": "target/obj/debug/src/main.cpp.o",
+    "arguments": ["clang++", "-DNAME=\"cum\"", "-c", "src/main.cpp"]
+  }
+]
+"#
+        );
+        assert_eq!(CompilationDatabase::default().to_json(), "[]\n");
+    }
+}
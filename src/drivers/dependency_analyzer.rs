@@ -1,84 +1,257 @@
 #![allow(dead_code, unused_variables)]
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
 
-use crate::{core::FilesystemManagerCell, errors::ExecutionError, parsing::config::Config};
+use threadpool::ThreadPool;
 
-use super::{fs_manager::FilesystemManager, proc_spawner::ProcSpawner};
+use crate::{
+    core::{DiagnosticsCell, FilesystemManagerCell},
+    errors::ExecutionError,
+    parsing::config::Config,
+};
+
+use super::{
+    build_database::{BuildDatabase, ObjectRecord},
+    fs_manager::FilesystemManager,
+    language::Language,
+    module_graph::ModuleGraph,
+    proc_spawner::ProcSpawner,
+};
 
 pub struct DependencyAnalyzer<'a> {
     fs_m: FilesystemManagerCell,
+    diagnostics: DiagnosticsCell,
     cfg: &'a Config,
+    thread_pool: &'a ThreadPool,
+    obj_dir: &'a Path,
 
     pub src_files: &'a Vec<PathBuf>,
-    pub dependency_spans: Vec<DependencySpan<'a>>,
+    pub dependency_spans: Vec<DependencySpan>,
+    /// Inputs missing from depfile of translation unit:
+    /// precompiled header and imported modules.
+    pub extra_dependencies: HashMap<PathBuf, Vec<PathBuf>>,
+    /// Arguments translation unit is compiled with, scan uses them without outputs.
+    pub compile_args: HashMap<PathBuf, Vec<String>>,
 }
 
 #[derive(Debug)]
-pub struct DependencySpan<'a> {
-    dependent: &'a Path,
+pub struct DependencySpan {
+    dependent: PathBuf,
     dependencies: Vec<PathBuf>,
 }
 
+/// Why translation unit has to be recompiled.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DirtyReason {
+    MissingObject,
+    UnreadableInput,
+    InputChanged(PathBuf),
+    CommandChanged,
+    /// Object was built before build database existed and is older than its inputs.
+    Outdated,
+}
+
 pub struct MakefileParser;
 
-// TODO: Distribute analyzis on thread pool.
 impl<'a> DependencyAnalyzer<'a> {
-    pub fn new(cfg: &'a Config, fs_m: FilesystemManagerCell, src_files: &'a Vec<PathBuf>) -> Self {
+    pub fn new(
+        cfg: &'a Config,
+        fs_m: FilesystemManagerCell,
+        diagnostics: DiagnosticsCell,
+        src_files: &'a Vec<PathBuf>,
+        thread_pool: &'a ThreadPool,
+        obj_dir: &'a Path,
+    ) -> Self {
         Self {
             cfg,
             fs_m,
+            diagnostics,
+            thread_pool,
+            obj_dir,
             src_files,
             dependency_spans: Vec::default(),
+            extra_dependencies: HashMap::default(),
+            compile_args: HashMap::default(),
         }
     }
 
-    pub fn generate_dependencies(&mut self) -> Result<(), ExecutionError> {
+    /// Dependencies are taken from depfile generated during last compilation,
+    /// clang is called (in parallel) only for objects which have no depfile yet.
+    /// Every failed scan is reported into diagnostics.
+    pub fn generate_dependencies(&mut self) {
+        let (tx, rx) = mpsc::channel();
+
         for file in self.src_files {
-            self.push_dependency(file)?;
+            if let Some(span) = self.known_dependency(file) {
+                self.dependency_spans.push(span);
+            } else {
+                ProcSpawner::spawn_into_pool(
+                    Language::of(file).compiler().into(),
+                    self.get_clang_args(file),
+                    file.clone(),
+                    self.thread_pool,
+                    tx.clone(),
+                );
+            }
         }
-        Ok(())
-    }
+        // Receiver stops as soon as all scans are done.
+        drop(tx);
 
-    /// Get entries from src_files which need to be recompiled.
-    pub fn get_dirty_src(&self) -> Vec<PathBuf> {
+        for (file, res) in rx.iter() {
+            match res {
+                Ok(o) if o.exit_code == 0 => self
+                    .dependency_spans
+                    .push(MakefileParser::make_dependency(&file, o.outs)),
+                Ok(o) => self.report_failed_scan(
+                    &file,
+                    ExecutionError::ProcErr {
+                        code: o.exit_code,
+                        errs: o.errs.trim().to_string(),
+                    },
+                ),
+                Err(err) => self.report_failed_scan(&file, err),
+            }
+        }
+        for span in &mut self.dependency_spans {
+            if let Some(extra) = self.extra_dependencies.get(&span.dependent) {
+                span.dependencies.extend_from_slice(extra);
+            }
+        }
+        // Keep order of compilation stable.
         self.dependency_spans
-            .iter()
-            .filter(|d| {
-                let obj = FilesystemManager::src_to_obj_path(d.dependent);
-                if !obj.exists() {
-                    return true;
-                }
+            .sort_by(|a, b| a.dependent.cmp(&b.dependent));
+    }
+
+    /// Get entries from src_files which need to be recompiled, along with the reason.
+    /// Entry is dirty if its object is missing, its command line fingerprint changed
+    /// or content of any input changed since last successful compilation.
+    pub fn get_dirty_src<F>(
+        &self,
+        db: &mut BuildDatabase,
+        fingerprint_of: F,
+    ) -> Vec<(PathBuf, DirtyReason)>
+    where
+        F: Fn(&Path) -> String,
+    {
+        let mut hashes = HashMap::default();
+        let mut res = Vec::default();
+
+        for d in &self.dependency_spans {
+            let obj = FilesystemManager::src_to_obj(&d.dependent, self.obj_dir);
+            let fingerprint = fingerprint_of(&d.dependent);
+            if let Some(reason) =
+                Self::dirty_reason(&obj, &d.dependencies, fingerprint, db, &mut hashes)
+            {
+                res.push((d.dependent.clone(), reason));
+            }
+        }
+
+        res
+    }
 
-                d.dependencies
+    /// Why output has to be rebuilt from its inputs, None if it is up to date.
+    pub fn dirty_reason(
+        output: &Path,
+        inputs: &[PathBuf],
+        fingerprint: String,
+        db: &mut BuildDatabase,
+        hashes: &mut HashMap<PathBuf, String>,
+    ) -> Option<DirtyReason> {
+        let current = ObjectRecord::from_inputs(inputs, fingerprint, hashes);
+
+        let reason = match (&current, db.get(output)) {
+            _ if !output.exists() => Some(DirtyReason::MissingObject),
+            (None, _) => Some(DirtyReason::UnreadableInput),
+            (Some(current), Some(recorded)) => Self::compare_records(current, recorded),
+            // Output was built before database existed, trust timestamps once.
+            (Some(_), None) => inputs
+                .iter()
+                .any(|p| FilesystemManager::is_newer(p, output).unwrap_or(true))
+                .then_some(DirtyReason::Outdated),
+        };
+
+        // Up to date output without record, remember its inputs from now on.
+        match current {
+            Some(current) if reason.is_none() && db.get(output).is_none() => {
+                db.insert(output, current)
+            }
+            _ => {}
+        }
+        reason
+    }
+
+    fn compare_records(current: &ObjectRecord, recorded: &ObjectRecord) -> Option<DirtyReason> {
+        if current.fingerprint != recorded.fingerprint {
+            return Some(DirtyReason::CommandChanged);
+        }
+        // Changed or added inputs first, then removed ones.
+        current
+            .inputs
+            .iter()
+            .find(|(path, hash)| recorded.inputs.get(*path) != Some(hash))
+            .or_else(|| {
+                recorded
+                    .inputs
                     .iter()
-                    .any(|p| FilesystemManager::is_newer(p, &obj).unwrap())
+                    .find(|(path, _)| !current.inputs.contains_key(*path))
             })
-            .map(|d| PathBuf::from(d.dependent))
-            .collect()
+            .map(|(path, _)| DirtyReason::InputChanged(path.into()))
     }
 
-    fn push_dependency(&mut self, file: &'a Path) -> Result<(), ExecutionError> {
-        let clang_output = ProcSpawner::spawn_and_wait("clang++", &self.get_clang_args(file))?;
-        if clang_output.exit_code != 0 {
-            return Err(ExecutionError::ProcErr {
-                code: clang_output.exit_code,
-                errs: clang_output.errs,
+    /// Dependencies which don't require clang to be called.
+    fn known_dependency(&self, file: &Path) -> Option<DependencySpan> {
+        let obj = FilesystemManager::src_to_obj(file, self.obj_dir);
+        if !obj.exists() || !Language::of(file).is_preprocessed() {
+            // Will be compiled anyway, no need to know its dependencies.
+            return Some(DependencySpan {
+                dependent: file.into(),
+                dependencies: vec![file.into()],
             });
         }
-        self.dependency_spans
-            .push(MakefileParser::make_dependency(file, clang_output.outs));
 
-        Ok(())
+        MakefileParser::read_depfile_of(&obj).map(|dependencies| DependencySpan {
+            dependent: file.into(),
+            dependencies,
+        })
+    }
+
+    fn report_failed_scan(&self, file: &Path, err: ExecutionError) {
+        self.diagnostics.borrow_mut().report_error_str(format!(
+            "Dependency scan of '{}' failed: {err}",
+            file.display()
+        ));
     }
 
     #[inline]
+    /// Compilation arguments without -c, outputs and precompiled header (which may be not built yet),
+    /// so defines, include directories and module files are the same as for compilation.
     fn get_clang_args(&self, file: &Path) -> Vec<String> {
-        let mut res = vec![
-            format!("-std=c++{}", self.cfg.std_as_str().unwrap()),
-            "-MM".into(),
-            "-E".into(),
-            file.display().to_string(),
-        ];
+        if let Some(args) = self.compile_args.get(file) {
+            let mut res = Vec::default();
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "-c" | "-MMD" => {}
+                    "-o" | "-MF" | "-include-pch" => {
+                        args.next();
+                    }
+                    _ => res.push(arg.clone()),
+                }
+            }
+            res.extend_from_slice(&["-MM".into(), "-E".into()]);
+            return res;
+        }
+        let mut res: Vec<String> = Language::of(file).std_flag(self.cfg).into_iter().collect();
+        res.extend_from_slice(&["-MM".into(), "-E".into()]);
+        if ModuleGraph::is_interface_unit(file) {
+            res.extend_from_slice(&["-x".into(), "c++-module".into()]);
+        }
+        res.push(file.display().to_string());
         for dir in &self.cfg.include_dirs {
             res.push(format!("-I{}", dir.display()));
         }
@@ -90,8 +263,22 @@ impl MakefileParser {
     /// file: dependent,
     /// str: dependencies in Makefile format.
     pub fn make_dependency(file: &Path, str: String) -> DependencySpan {
-        let dependencies = str
-            .lines()
+        DependencySpan {
+            dependent: file.into(),
+            dependencies: Self::parse_dependencies(&str),
+        }
+    }
+
+    /// Dependencies from depfile written next to output during its compilation.
+    pub fn read_depfile_of(output: &Path) -> Option<Vec<PathBuf>> {
+        fs::read_to_string(FilesystemManager::obj_to_dep(output))
+            .ok()
+            .map(|str| Self::parse_dependencies(&str))
+    }
+
+    /// Prerequisites from Makefile rule, target is skipped.
+    pub fn parse_dependencies(str: &str) -> Vec<PathBuf> {
+        str.lines()
             .flat_map(|line| {
                 let line = line.trim_end();
                 let line = line.trim_end_matches('\\').trim();
@@ -103,34 +290,144 @@ impl MakefileParser {
                 parts.into_iter()
             })
             .map(PathBuf::from)
-            .collect();
+            .collect()
+    }
+}
 
-        DependencySpan {
-            dependent: file,
-            dependencies,
+impl Display for DirtyReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DirtyReason::MissingObject => write!(f, "object file is missing"),
+            DirtyReason::UnreadableInput => write!(f, "some of inputs can't be read"),
+            DirtyReason::InputChanged(path) => write!(f, "'{}' changed", path.display()),
+            DirtyReason::CommandChanged => write!(f, "compiler or its flags changed"),
+            DirtyReason::Outdated => write!(f, "object file is outdated"),
         }
     }
 }
 
+#[cfg(test)]
 pub mod tests {
-    use super::DependencyAnalyzer;
+    use super::{DependencyAnalyzer, DirtyReason, MakefileParser};
     use crate::{
-        core::FilesystemManagerCell,
-        parsing::config_parser::ConfigParser,
+        core::{DiagnosticsCell, FilesystemManagerCell},
+        drivers::{build_database::ObjectRecord, fs_manager::FilesystemManager},
         test_utils::{set_dir_to_tests, MockFactory},
     };
     use std::path::PathBuf;
+    use threadpool::ThreadPool;
 
     #[test]
     fn simple_dep_anal_debug() {
-        set_dir_to_tests();
+        let _dir = set_dir_to_tests();
         let fs_m = FilesystemManagerCell::default();
         let mock_cfg = MockFactory::mock_cfg_default();
+        let diagnostics = DiagnosticsCell::default();
         let mock_files: Vec<PathBuf> = vec!["src/main.cpp".into(), "src/dep1.cpp".into()];
-        let mut analyzer = DependencyAnalyzer::new(&mock_cfg, fs_m, &mock_files);
+        let thread_pool = ThreadPool::default();
+        let obj_dir = FilesystemManager::obj_dir("debug");
+        let mut analyzer = DependencyAnalyzer::new(
+            &mock_cfg,
+            fs_m,
+            diagnostics.clone(),
+            &mock_files,
+            &thread_pool,
+            &obj_dir,
+        );
 
-        analyzer.generate_dependencies().unwrap();
+        analyzer.generate_dependencies();
+
+        assert!(!diagnostics.borrow().contains_error());
 
         println!("Generated: {:#?}", analyzer.dependency_spans);
     }
+
+    #[test]
+    fn scan_args_follow_compilation() {
+        let fs_m = FilesystemManagerCell::default();
+        let mock_cfg = MockFactory::mock_cfg_default();
+        let mock_files: Vec<PathBuf> = vec!["src/main.cpp".into()];
+        let thread_pool = ThreadPool::default();
+        let obj_dir = FilesystemManager::obj_dir("debug");
+        let mut analyzer = DependencyAnalyzer::new(
+            &mock_cfg,
+            fs_m,
+            DiagnosticsCell::default(),
+            &mock_files,
+            &thread_pool,
+            &obj_dir,
+        );
+        let args = "-std=c++20 -DFEATURE -Ideps/include -include-pch target/obj/debug/pch.pch \
+                    -fmodule-file=core=target/obj/debug/core.pcm -c src/main.cpp \
+                    -o target/obj/debug/src/main.cpp.o -MMD -MF target/obj/debug/src/main.cpp.d";
+        analyzer.compile_args.insert(
+            "src/main.cpp".into(),
+            args.split_whitespace().map(String::from).collect(),
+        );
+
+        assert_eq!(
+            analyzer.get_clang_args(&PathBuf::from("src/main.cpp")),
+            [
+                "-std=c++20",
+                "-DFEATURE",
+                "-Ideps/include",
+                "-fmodule-file=core=target/obj/debug/core.pcm",
+                "src/main.cpp",
+                "-MM",
+                "-E"
+            ]
+        );
+    }
+
+    #[test]
+    fn makefile_parser_depfile() {
+        let depfile =
+            "target/obj/debug/src.main.o: src/main.cpp include/file.hpp \\\n  include/file1.hpp\n";
+        let deps = MakefileParser::parse_dependencies(depfile);
+
+        assert_eq!(
+            deps,
+            vec![
+                PathBuf::from("src/main.cpp"),
+                PathBuf::from("include/file.hpp"),
+                PathBuf::from("include/file1.hpp"),
+            ]
+        );
+    }
+
+    #[test]
+    fn dirty_reason_from_records() {
+        let mut recorded = ObjectRecord {
+            fingerprint: "fp".into(),
+            ..Default::default()
+        };
+        recorded.inputs.insert("src/main.cpp".into(), "a".into());
+        recorded
+            .inputs
+            .insert("include/file.hpp".into(), "b".into());
+
+        let same = recorded.clone();
+        assert_eq!(DependencyAnalyzer::compare_records(&same, &recorded), None);
+
+        let mut flags = recorded.clone();
+        flags.fingerprint = "other".into();
+        assert_eq!(
+            DependencyAnalyzer::compare_records(&flags, &recorded),
+            Some(DirtyReason::CommandChanged)
+        );
+
+        let mut header = recorded.clone();
+        header.inputs.insert("include/file.hpp".into(), "c".into());
+        assert_eq!(
+            DependencyAnalyzer::compare_records(&header, &recorded),
+            Some(DirtyReason::InputChanged("include/file.hpp".into()))
+        );
+
+        let mut removed = recorded.clone();
+        removed.inputs.remove("include/file.hpp");
+        assert_eq!(
+            DependencyAnalyzer::compare_records(&removed, &recorded),
+            Some(DirtyReason::InputChanged("include/file.hpp".into()))
+        );
+    }
 }
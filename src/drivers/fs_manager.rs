@@ -1,11 +1,21 @@
 use std::{
     env,
     fs::{self, File},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
+use ignore::{
+    overrides::{Override, OverrideBuilder},
+    WalkBuilder,
+};
+use path_clean::PathClean;
+use sha2::{Digest, Sha256};
 use walkdir::WalkDir;
 
+use crate::parsing::config::Config;
+
+pub const OBJ_ROOT: &str = "target/obj";
+
 pub struct FilesystemManager {
     root: PathBuf,
 }
@@ -37,6 +47,92 @@ impl FilesystemManager {
             .collect()
     }
 
+    /// All pathes are relative to root
+    pub fn find_all_files(&self, dir: &Path) -> Vec<PathBuf> {
+        if !dir.exists() {
+            return vec![];
+        }
+        WalkDir::new(self.root.join(dir))
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+            .filter_map(|e| e.path().strip_prefix(&self.root).ok().map(PathBuf::from))
+            .collect()
+    }
+
+    /// Sources with configured extensions inside of source directories (or listed directly),
+    /// except excluded and (if enabled) git ignored ones. Pathes are relative to root.
+    pub fn find_sources(&self, cfg: &Config, dirs: &[PathBuf]) -> Vec<PathBuf> {
+        let dirs: Vec<PathBuf> = dirs
+            .iter()
+            .map(|d| self.root.join(d.clean()))
+            .filter(|d| d.exists())
+            .collect();
+        let Some((first, rest)) = dirs.split_first() else {
+            return vec![];
+        };
+
+        let mut overrides = OverrideBuilder::new(&self.root);
+        for pattern in &cfg.exclude {
+            // Patterns are validated along with config.
+            let _ = overrides.add(&format!("!{pattern}"));
+        }
+        let gitignore = cfg.gitignore.unwrap_or(false);
+
+        let mut walker = WalkBuilder::new(first);
+        for dir in rest {
+            walker.add(dir);
+        }
+        walker
+            .standard_filters(false)
+            .git_ignore(gitignore)
+            .git_exclude(gitignore)
+            .parents(gitignore)
+            .require_git(false)
+            .overrides(overrides.build().unwrap_or_else(|_| Override::empty()));
+
+        let mut res: Vec<PathBuf> = walker
+            .build()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+            .filter(|e| {
+                e.path()
+                    .extension()
+                    .and_then(|s| s.to_str())
+                    .is_some_and(|s| cfg.extensions.iter().any(|ext| ext == s))
+            })
+            .filter_map(|e| e.path().strip_prefix(&self.root).ok().map(PathBuf::from))
+            .collect();
+        // Nested or repeated source directories yield the same files twice.
+        res.sort();
+        res.dedup();
+        res
+    }
+
+    /// Files directly inside of dir, pathes are relative to root
+    pub fn list_files(&self, dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(self.root.join(dir)) else {
+            return vec![];
+        };
+        entries
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+            .map(|e| dir.join(e.file_name()))
+            .collect()
+    }
+
+    /// Directories directly inside of dir, pathes are relative to root
+    pub fn list_dirs(&self, dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(self.root.join(dir)) else {
+            return vec![];
+        };
+        entries
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+            .map(|e| dir.join(e.file_name()))
+            .collect()
+    }
+
     #[inline]
     pub fn find_newest(list: &[PathBuf]) -> Option<PathBuf> {
         list.iter()
@@ -67,17 +163,45 @@ impl FilesystemManager {
         Ok(())
     }
 
-    /// Path is relative to root
+    /// Path is relative to root, symlink is removed itself (even if it is dangling).
     #[inline]
     pub fn delete(&self, path: &Path) -> std::io::Result<()> {
         let path = self.root.join(path);
-        if path.exists() && path.is_file() {
-            fs::remove_file(path)
-        } else if path.exists() && path.is_dir() {
-            fs::remove_dir_all(path)
-        } else {
-            Ok(())
+        match path.symlink_metadata() {
+            Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+            Ok(_) => fs::remove_file(path),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Pathes are relative to root, existing file is replaced.
+    pub fn copy(&self, source: &Path, path: &Path) -> std::io::Result<()> {
+        let path = self.root.join(path);
+        if path.symlink_metadata().is_ok() {
+            fs::remove_file(&path)?;
+        }
+        fs::copy(self.root.join(source), path).map(|_| ())
+    }
+
+    /// Path is relative to root
+    #[inline]
+    pub fn write(&self, path: &Path, content: &str) -> std::io::Result<()> {
+        let path = self.root.join(path);
+        path.parent().map(fs::create_dir_all).unwrap_or(Ok(()))?;
+        fs::write(path, content)
+    }
+
+    /// Path is relative to root, original is relative to directory of link.
+    /// Existing link is replaced.
+    pub fn symlink(&self, path: &Path, original: &Path) -> std::io::Result<()> {
+        let path = self.root.join(path);
+        if path.symlink_metadata().is_ok() {
+            fs::remove_file(&path)?;
         }
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(original, path);
+        #[cfg(windows)]
+        return std::os::windows::fs::symlink_file(original, path);
     }
 
     /// Path is relative to root
@@ -104,31 +228,59 @@ impl FilesystemManager {
         Some(!Self::is_older(first, second)?)
     }
 
+    /// Hex encoded sha256 of file content.
+    pub fn hash_file(path: &Path) -> Option<String> {
+        let content = fs::read(path).ok()?;
+        Some(format!("{:x}", Sha256::digest(content)))
+    }
+
     #[inline]
     pub fn to_full(&self, path: &Path) -> PathBuf {
         self.root.join(path)
     }
 
-    /// src/deps/dep1.cpp turns into target/obj/src.deps.dep1.o
-    pub fn src_to_obj(path: &PathBuf) -> PathBuf {
-        let dotted: String = path
-            .with_extension("o")
-            .components()
-            .map(|c| c.as_os_str().to_string_lossy().to_string())
-            .collect::<Vec<_>>()
-            .join(".");
-        PathBuf::from(format!("target/obj/{dotted}"))
+    /// Every preset has its own objects: debug turns into target/obj/debug
+    #[inline]
+    pub fn obj_dir(preset: &str) -> PathBuf {
+        PathBuf::from(OBJ_ROOT).join(preset)
     }
 
-    /// src/deps/dep1.cpp turns into target/obj/src.deps.dep1.o
-    pub fn src_to_obj_path(path: &Path) -> PathBuf {
-        let dotted: String = path
-            .with_extension("o")
-            .components()
-            .map(|c| c.as_os_str().to_string_lossy().to_string())
-            .collect::<Vec<_>>()
-            .join(".");
-        PathBuf::from(format!("target/obj/{dotted}"))
+    /// src/deps/dep1.cpp turns into <obj_dir>/src/deps/dep1.cpp.o
+    /// Source extension is kept, so dep1.c and dep1.cpp never share an object.
+    /// '..' turns into '__' to keep objects inside of obj_dir.
+    pub fn src_to_obj(path: &Path, obj_dir: &Path) -> PathBuf {
+        let mut res = obj_dir.to_path_buf();
+        for c in path.components() {
+            match c {
+                Component::Normal(s) => res.push(s),
+                Component::ParentDir => res.push("__"),
+                _ => {}
+            }
+        }
+        let mut name = res.file_name().unwrap_or_default().to_os_string();
+        name.push(".o");
+        res.set_file_name(name);
+        res
+    }
+
+    /// include/pch.hpp turns into <obj_dir>/include/pch.hpp.pch
+    #[inline]
+    pub fn src_to_pch(path: &Path, obj_dir: &Path) -> PathBuf {
+        Self::src_to_obj(path, obj_dir).with_extension("pch")
+    }
+
+    /// Module math:ops turns into <obj_dir>/modules/math-ops.pcm
+    #[inline]
+    pub fn module_to_bmi(name: &str, obj_dir: &Path) -> PathBuf {
+        obj_dir
+            .join("modules")
+            .join(format!("{}.pcm", name.replace(':', "-")))
+    }
+
+    /// Depfile is stored next to its object: main.cpp.o -> main.cpp.d
+    #[inline]
+    pub fn obj_to_dep(path: &Path) -> PathBuf {
+        path.with_extension("d")
     }
 
     pub fn root(&self) -> &PathBuf {
@@ -143,3 +295,63 @@ impl Default for FilesystemManager {
         }
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::FilesystemManager;
+    use crate::test_utils::{set_dir_to_tests, MockFactory};
+
+    #[test]
+    fn find_sources_with_excludes() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManager::default();
+        let mut cfg = MockFactory::mock_cfg_default();
+
+        assert_eq!(
+            fs_m.find_sources(&cfg, &cfg.sources),
+            vec![PathBuf::from("src/dep1.cpp"), PathBuf::from("src/main.cpp")]
+        );
+        assert_eq!(
+            fs_m.find_sources(&cfg, &["src/dep1.cpp".into()]),
+            vec![PathBuf::from("src/dep1.cpp")]
+        );
+
+        cfg.exclude = vec!["src/dep*.cpp".into()];
+        cfg.sources.push("src".into());
+        assert_eq!(
+            fs_m.find_sources(&cfg, &cfg.sources),
+            vec![PathBuf::from("src/main.cpp")]
+        );
+
+        cfg.extensions = vec!["c".into()];
+        assert!(fs_m.find_sources(&cfg, &cfg.sources).is_empty());
+    }
+
+    #[test]
+    fn src_to_obj_mirrors_tree() {
+        let obj_dir = FilesystemManager::obj_dir("debug");
+        let first = FilesystemManager::src_to_obj(Path::new("src/a.b/c.cpp"), &obj_dir);
+        let second = FilesystemManager::src_to_obj(Path::new("src/a/b.c.cpp"), &obj_dir);
+
+        assert_eq!(first, PathBuf::from("target/obj/debug/src/a.b/c.cpp.o"));
+        assert_eq!(second, PathBuf::from("target/obj/debug/src/a/b.c.cpp.o"));
+        assert_eq!(
+            FilesystemManager::obj_to_dep(&first),
+            PathBuf::from("target/obj/debug/src/a.b/c.cpp.d")
+        );
+        assert_eq!(
+            FilesystemManager::src_to_obj(Path::new("../shared/x.cpp"), &obj_dir),
+            PathBuf::from("target/obj/debug/__/shared/x.cpp.o")
+        );
+        assert_eq!(
+            FilesystemManager::src_to_pch(Path::new("include/pch.hpp"), &obj_dir),
+            PathBuf::from("target/obj/debug/include/pch.hpp.pch")
+        );
+        assert_eq!(
+            FilesystemManager::module_to_bmi("math:ops", &obj_dir),
+            PathBuf::from("target/obj/debug/modules/math-ops.pcm")
+        );
+    }
+}
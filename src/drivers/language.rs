@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use crate::{
+    meta::{COMPILER, C_COMPILER},
+    parsing::config::Config,
+};
+
+/// Names of languages in preset 'lang_cflags' table.
+pub const LANGUAGE_NAMES: [&str; 4] = ["cpp", "c", "objcpp", "asm"];
+
+/// Language of source file, decides compiler and flags it is compiled with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Cpp,
+    C,
+    ObjCpp,
+    /// '.S', passed through preprocessor before assembling.
+    PreprocessedAsm,
+    /// '.s', assembled as is.
+    Asm,
+}
+
+impl Language {
+    /// Entry points may have any extension, unknown ones are treated as C++.
+    pub fn of(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+        {
+            "c" => Self::C,
+            "mm" => Self::ObjCpp,
+            "S" => Self::PreprocessedAsm,
+            "s" => Self::Asm,
+            _ => Self::Cpp,
+        }
+    }
+
+    /// Key of language specific flags in preset.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cpp => "cpp",
+            Self::C => "c",
+            Self::ObjCpp => "objcpp",
+            Self::PreprocessedAsm | Self::Asm => "asm",
+        }
+    }
+
+    pub fn compiler(self) -> &'static str {
+        match self {
+            Self::Cpp | Self::ObjCpp => COMPILER,
+            Self::C | Self::PreprocessedAsm | Self::Asm => C_COMPILER,
+        }
+    }
+
+    /// None for assembly, it has no standard.
+    pub fn std_flag(self, cfg: &Config) -> Option<String> {
+        match self {
+            Self::Cpp | Self::ObjCpp => Some(format!("-std=c++{}", cfg.std_as_str()?)),
+            Self::C => Some(format!("-std=c{}", cfg.c_std_as_str()?)),
+            Self::PreprocessedAsm | Self::Asm => None,
+        }
+    }
+
+    /// Plain assembly can't include anything, so it has no dependencies but itself.
+    #[inline]
+    pub fn is_preprocessed(self) -> bool {
+        self != Self::Asm
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::path::Path;
+
+    use super::Language;
+    use crate::test_utils::MockFactory;
+
+    #[test]
+    fn language_rules() {
+        let cfg = MockFactory::mock_cfg_default();
+        let c = Language::of(Path::new("src/lib/util.c"));
+        let asm = Language::of(Path::new("src/start.s"));
+
+        assert_eq!(c, Language::C);
+        assert_eq!(c.compiler(), "clang");
+        assert_eq!(c.std_flag(&cfg), Some("-std=c17".into()));
+        assert_eq!(Language::of(Path::new("src/a.cxx")), Language::Cpp);
+        assert_eq!(Language::of(Path::new("src/view.mm")).name(), "objcpp");
+        assert_eq!(
+            Language::of(Path::new("src/crt.S")),
+            Language::PreprocessedAsm
+        );
+        assert_eq!(asm.std_flag(&cfg), None);
+        assert!(!asm.is_preprocessed());
+    }
+}
@@ -1,3 +1,10 @@
+pub mod build_database;
+pub mod compilation_database;
 pub mod dependency_analyzer;
 pub mod fs_manager;
+pub mod language;
+pub mod module_graph;
+pub mod object_cache;
+pub mod pkg_config;
 pub mod proc_spawner;
+pub mod vendor;
@@ -0,0 +1,238 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::errors::PlannerError;
+
+use super::language::Language;
+
+/// Extensions of module interface units.
+pub const MODULE_EXTENSIONS: [&str; 2] = ["cppm", "ixx"];
+
+/// Named modules of the project and imports of every translation unit.
+/// Built by scanning sources for 'export module', 'module' and 'import' declarations.
+#[derive(Debug, Default, Clone)]
+pub struct ModuleGraph {
+    /// Module name -> interface unit which exports it.
+    interfaces: BTreeMap<String, PathBuf>,
+    /// Translation unit -> modules it imports directly.
+    imports: HashMap<PathBuf, BTreeSet<String>>,
+}
+
+/// Module declarations found in single translation unit.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct UnitDeclarations {
+    exports: Option<String>,
+    imports: BTreeSet<String>,
+}
+
+impl ModuleGraph {
+    /// Only C++ sources can use modules.
+    /// Unreadable sources are skipped, compiler will report them itself.
+    pub fn scan(sources: &[PathBuf]) -> Result<Self, PlannerError> {
+        let mut res = Self::default();
+
+        for src in sources.iter().filter(|s| Language::of(s) == Language::Cpp) {
+            let Ok(str) = fs::read_to_string(src) else {
+                continue;
+            };
+            let decl = Self::parse_declarations(&str, Self::is_interface_unit(src));
+
+            if let Some(name) = decl.exports {
+                if let Some(other) = res.interfaces.get(&name) {
+                    return Err(PlannerError::DuplicateModule(
+                        name,
+                        other.clone(),
+                        src.clone(),
+                    ));
+                }
+                res.interfaces.insert(name, src.clone());
+            }
+            if !decl.imports.is_empty() {
+                res.imports.insert(src.clone(), decl.imports);
+            }
+        }
+
+        for (src, imports) in &res.imports {
+            if let Some(name) = imports.iter().find(|n| !res.interfaces.contains_key(*n)) {
+                return Err(PlannerError::UnknownModule(name.clone(), src.clone()));
+            }
+        }
+        Ok(res)
+    }
+
+    #[inline]
+    pub fn is_interface_unit(path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| MODULE_EXTENSIONS.contains(&e))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.interfaces.is_empty()
+    }
+
+    /// Pairs of module name and its interface unit.
+    pub fn interfaces(&self) -> impl Iterator<Item = (&String, &PathBuf)> {
+        self.interfaces.iter()
+    }
+
+    #[inline]
+    pub fn interface_of(&self, name: &str) -> Option<&PathBuf> {
+        self.interfaces.get(name)
+    }
+
+    /// Every module translation unit depends on, including imports of imported modules.
+    pub fn imports_of(&self, src: &Path) -> BTreeSet<String> {
+        let mut res = BTreeSet::default();
+        let mut stack: Vec<&String> = self.imports.get(src).into_iter().flatten().collect();
+
+        while let Some(name) = stack.pop() {
+            if !res.insert(name.clone()) {
+                continue;
+            }
+            if let Some(imports) = self
+                .interfaces
+                .get(name)
+                .and_then(|interface| self.imports.get(interface))
+            {
+                stack.extend(imports);
+            }
+        }
+        res
+    }
+
+    /// Modules grouped in order they have to be precompiled,
+    /// every module imports only modules from previous groups.
+    pub fn levels(&self) -> Result<Vec<Vec<String>>, PlannerError> {
+        let mut res: Vec<Vec<String>> = Vec::default();
+        let mut remaining: BTreeMap<&String, BTreeSet<&String>> = self
+            .interfaces
+            .iter()
+            .map(|(name, src)| (name, self.imports.get(src).into_iter().flatten().collect()))
+            .collect();
+
+        while !remaining.is_empty() {
+            let level: Vec<String> = remaining
+                .iter()
+                .filter(|(_, imports)| imports.is_empty())
+                .map(|(name, _)| (*name).clone())
+                .collect();
+
+            if level.is_empty() {
+                let cycle: Vec<&str> = remaining.keys().map(|n| n.as_str()).collect();
+                return Err(PlannerError::ModuleCycle(cycle.join(", ")));
+            }
+            for name in &level {
+                remaining.remove(name);
+            }
+            for imports in remaining.values_mut() {
+                imports.retain(|n| !level.contains(n));
+            }
+            res.push(level);
+        }
+        Ok(res)
+    }
+
+    /// Only declarations at the start of line are recognized,
+    /// header units ('import <vector>;') are left to compiler.
+    fn parse_declarations(str: &str, interface: bool) -> UnitDeclarations {
+        let mut res = UnitDeclarations::default();
+        // Primary module name, partitions ('import :part;') are relative to it.
+        let mut primary = String::default();
+
+        for line in str.lines() {
+            let line = line.split("//").next().unwrap_or_default().trim();
+            let Some(decl) = line.strip_suffix(';') else {
+                continue;
+            };
+            let mut words: Vec<&str> = decl.split_whitespace().collect();
+            let exported = words.first() == Some(&"export");
+            if exported {
+                words.remove(0);
+            }
+
+            match words.as_slice() {
+                ["module", name] if !name.starts_with(':') => {
+                    primary = name.split(':').next().unwrap_or_default().to_string();
+                    if exported && interface {
+                        res.exports = Some(name.to_string());
+                    } else if !exported && !name.contains(':') {
+                        // Implementation unit imports its interface implicitly.
+                        res.imports.insert(name.to_string());
+                    }
+                }
+                ["import", name] if name.starts_with(':') => {
+                    res.imports.insert(format!("{primary}{name}"));
+                }
+                ["import", name] if !name.starts_with(['<', '"']) => {
+                    res.imports.insert(name.to_string());
+                }
+                _ => {}
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+    use super::{ModuleGraph, UnitDeclarations};
+
+    #[test]
+    fn module_declarations_parsing() {
+        let interface = "module;\n#include <vector>\nexport module math:ops;\nimport :base;\nexport import util; // comment\nimport <string>;\n";
+        assert_eq!(
+            ModuleGraph::parse_declarations(interface, true),
+            UnitDeclarations {
+                exports: Some("math:ops".into()),
+                imports: BTreeSet::from(["math:base".into(), "util".into()]),
+            }
+        );
+
+        let implementation = "module math;\nimport std_like;\nmodule :private;\n";
+        assert_eq!(
+            ModuleGraph::parse_declarations(implementation, false),
+            UnitDeclarations {
+                exports: None,
+                imports: BTreeSet::from(["math".into(), "std_like".into()]),
+            }
+        );
+    }
+
+    #[test]
+    fn module_graph_levels_and_imports() {
+        let graph = ModuleGraph {
+            interfaces: BTreeMap::from([
+                ("a".into(), "src/a.cppm".into()),
+                ("b".into(), "src/b.cppm".into()),
+                ("c".into(), "src/c.ixx".into()),
+            ]),
+            imports: HashMap::from([
+                ("src/b.cppm".into(), BTreeSet::from(["a".into()])),
+                ("src/c.ixx".into(), BTreeSet::from(["b".into()])),
+                ("src/main.cpp".into(), BTreeSet::from(["c".into()])),
+            ]),
+        };
+
+        assert_eq!(
+            graph.levels().unwrap(),
+            vec![vec!["a".to_string()], vec!["b".into()], vec!["c".into()]]
+        );
+        assert_eq!(
+            graph.imports_of("src/main.cpp".as_ref()),
+            BTreeSet::from(["a".into(), "b".into(), "c".into()])
+        );
+
+        let mut cyclic = graph.clone();
+        cyclic
+            .imports
+            .insert("src/a.cppm".into(), BTreeSet::from(["c".into()]));
+        assert!(cyclic.levels().is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    env,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::parsing::config::CacheConfig;
+
+/// Objects are kept apart from other content of cache directory (fetched sources).
+pub const OBJECTS_DIR: &str = "objects";
+
+/// Distinguishes temporary files of concurrent stores within process.
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Content addressed storage of objects shared between projects.
+/// Every entry is <dir>/<key[..2]>/<key>.o along with its depfile <key>.d (if source has one),
+/// where 'dir' is 'objects' inside of user cache directory.
+#[derive(Debug, Clone)]
+pub struct ObjectCache {
+    dir: PathBuf,
+    /// Bytes.
+    max_size: u64,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub entries: usize,
+    /// Bytes.
+    pub size: u64,
+}
+
+impl ObjectCache {
+    pub fn new(dir: PathBuf, max_size_mb: u64) -> Self {
+        Self {
+            dir,
+            max_size: max_size_mb * 1024 * 1024,
+        }
+    }
+
+    /// None if cache is disabled or there is no place for it.
+    pub fn from_config(cfg: &CacheConfig) -> Option<Self> {
+        if !cfg.enabled.unwrap_or(false) {
+            return None;
+        }
+        Some(Self::new(
+            Self::objects_dir()?,
+            cfg.max_size_mb.unwrap_or(0),
+        ))
+    }
+
+    #[inline]
+    pub fn objects_dir() -> Option<PathBuf> {
+        Self::user_dir().map(|d| d.join(OBJECTS_DIR))
+    }
+
+    /// $CUM_CACHE_DIR, or 'cum' inside of user cache directory.
+    pub fn user_dir() -> Option<PathBuf> {
+        if let Some(dir) = env::var_os("CUM_CACHE_DIR") {
+            return Some(dir.into());
+        }
+        if cfg!(target_os = "windows") {
+            return env::var_os("LOCALAPPDATA").map(|p| PathBuf::from(p).join("cum"));
+        }
+        env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|p| PathBuf::from(p).join(".cache")))
+            .map(|p| p.join("cum"))
+    }
+
+    /// Key depends on everything which affects produced object:
+    /// compiler, command line without output pathes and preprocessed source.
+    pub fn key(compiler_version: &str, args: &[String], preprocessed: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(compiler_version.as_bytes());
+        for arg in args {
+            hasher.update([0]);
+            hasher.update(arg.as_bytes());
+        }
+        hasher.update([0]);
+        hasher.update(preprocessed);
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Copy cached object and depfile into place, false on cache miss.
+    pub fn fetch(&self, key: &str, obj: &Path, depfile: Option<&Path>) -> bool {
+        let entry = self.entry(key, "o");
+        if !entry.exists() {
+            return false;
+        }
+        let copied = fs::copy(&entry, obj).is_ok()
+            && depfile.is_none_or(|d| fs::copy(self.entry(key, "d"), d).is_ok());
+        if copied {
+            // Eviction removes least recently used entries first.
+            let _ = File::options()
+                .write(true)
+                .open(&entry)
+                .and_then(|f| f.set_modified(SystemTime::now()));
+        }
+        copied
+    }
+
+    /// Entries are written under temporary names unique to every write and renamed,
+    /// so concurrent builds (and threads storing same key) never see partially written objects.
+    pub fn store(&self, key: &str, obj: &Path, depfile: Option<&Path>) -> io::Result<()> {
+        let entry = self.entry(key, "o");
+        if let Some(parent) = entry.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Depfile goes first, object presence marks complete entry.
+        let depfile = depfile.map(|d| (d, self.entry(key, "d")));
+        for (src, dest) in depfile.into_iter().chain([(obj, entry)]) {
+            let n = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let tmp = dest.with_extension(format!("tmp{}-{n}", process::id()));
+            let res = fs::copy(src, &tmp).and_then(|_| fs::rename(&tmp, &dest));
+            if res.is_err() {
+                let _ = fs::remove_file(&tmp);
+            }
+            res?;
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let mut res = CacheStats::default();
+        for (path, size, _) in self.files() {
+            res.size += size;
+            if path.extension().is_some_and(|e| e == "o") {
+                res.entries += 1;
+            }
+        }
+        res
+    }
+
+    /// Remove least recently used entries until cache fits into its size limit.
+    /// Returns count of removed entries.
+    pub fn evict(&self) -> usize {
+        let mut objects: Vec<(PathBuf, u64, SystemTime)> = Vec::default();
+        let mut size = 0;
+        for (path, len, modified) in self.files() {
+            size += len;
+            if path.extension().is_some_and(|e| e == "o") {
+                let dep_len = fs::metadata(path.with_extension("d"))
+                    .map(|m| m.len())
+                    .unwrap_or(0);
+                objects.push((path, len + dep_len, modified));
+            }
+        }
+        if size <= self.max_size {
+            return 0;
+        }
+
+        objects.sort_by_key(|(_, _, modified)| *modified);
+        let mut removed = 0;
+        for (path, len, _) in objects {
+            if size <= self.max_size {
+                break;
+            }
+            let _ = fs::remove_file(&path);
+            let _ = fs::remove_file(path.with_extension("d"));
+            size = size.saturating_sub(len);
+            removed += 1;
+        }
+        removed
+    }
+
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    #[inline]
+    fn entry(&self, key: &str, ext: &str) -> PathBuf {
+        self.dir
+            .join(&key[..2.min(key.len())])
+            .join(key)
+            .with_extension(ext)
+    }
+
+    fn files(&self) -> impl Iterator<Item = (PathBuf, u64, SystemTime)> {
+        WalkDir::new(&self.dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+            .filter_map(|e| {
+                let meta = e.metadata().ok()?;
+                Some((e.into_path(), meta.len(), meta.modified().ok()?))
+            })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{env, fs, process, thread};
+
+    use super::ObjectCache;
+
+    #[test]
+    fn object_cache_store_fetch_evict() {
+        let root = env::temp_dir().join(format!("cum_cache_test_{}", process::id()));
+        let work = root.join("work");
+        fs::create_dir_all(&work).unwrap();
+        let cache = ObjectCache::new(root.join("cache"), 1);
+
+        let (obj, dep) = (work.join("main.cpp.o"), work.join("main.cpp.d"));
+        fs::write(&obj, "object").unwrap();
+        fs::write(&dep, "main.cpp.o: main.cpp").unwrap();
+
+        let key = ObjectCache::key("clang 18", &["-O0".into()], b"int main() {}");
+        assert!(!cache.fetch(&key, &obj, Some(&dep)));
+        cache.store(&key, &obj, Some(&dep)).unwrap();
+        assert_eq!(cache.stats().entries, 1);
+
+        fs::remove_file(&obj).unwrap();
+        assert!(cache.fetch(&key, &obj, Some(&dep)));
+        assert_eq!(fs::read_to_string(&obj).unwrap(), "object");
+
+        // Plain assembly has no depfile.
+        let asm_key = ObjectCache::key("clang 18", &["-c".into()], b"ret");
+        cache.store(&asm_key, &obj, None).unwrap();
+        fs::remove_file(&obj).unwrap();
+        assert!(cache.fetch(&asm_key, &obj, None));
+        assert_eq!(cache.stats().entries, 2);
+
+        // Over the limit (1 MB) only after big entry is added.
+        assert_eq!(cache.evict(), 0);
+        let big_key = ObjectCache::key("clang 18", &["-O3".into()], b"int main() {}");
+        fs::write(&obj, vec![0u8; 2 * 1024 * 1024]).unwrap();
+        cache.store(&big_key, &obj, Some(&dep)).unwrap();
+        assert!(cache.evict() > 0);
+        assert!(cache.stats().size <= 1024 * 1024);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn object_cache_concurrent_stores_of_same_key() {
+        let root = env::temp_dir().join(format!("cum_cache_race_test_{}", process::id()));
+        let cache = ObjectCache::new(root.join("cache"), 16);
+        let key = ObjectCache::key("clang 18", &["-O2".into()], b"int f();");
+
+        let workers: Vec<_> = (0..8)
+            .map(|i| {
+                let (cache, key, work) = (cache.clone(), key.clone(), root.join(i.to_string()));
+                thread::spawn(move || {
+                    fs::create_dir_all(&work).unwrap();
+                    let (obj, dep) = (work.join("f.cpp.o"), work.join("f.cpp.d"));
+                    fs::write(&obj, vec![b'o'; 64 * 1024]).unwrap();
+                    fs::write(&dep, "f.cpp.o: f.cpp").unwrap();
+                    cache.store(&key, &obj, Some(&dep)).unwrap();
+                })
+            })
+            .collect();
+        workers.into_iter().for_each(|w| w.join().unwrap());
+
+        // Single complete entry, no temporary files left.
+        assert_eq!(cache.stats().entries, 1);
+        assert_eq!(
+            cache.stats().size,
+            64 * 1024 + "f.cpp.o: f.cpp".len() as u64
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
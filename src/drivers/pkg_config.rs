@@ -0,0 +1,292 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{errors::ParsingError, logger::Logger};
+
+use super::proc_spawner::ProcSpawner;
+
+pub const PKG_CONFIG: &str = "pkg-config";
+pub const PKG_CONFIG_CACHE_PATH: &str = "target/pkg_config.toml";
+
+/// Compiler and linker flags of system packages, merged into every command line.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageFlags {
+    /// Hash of queried packages, every .pc file they resolve to (with 'Requires')
+    /// and pkg-config environment, cache is dropped once any of them changes.
+    #[serde(default)]
+    key: String,
+    /// Directories .pc files are searched in, default ones are only known to pkg-config.
+    #[serde(default)]
+    pc_dirs: Vec<PathBuf>,
+    #[serde(default)]
+    pub cflags: Vec<String>,
+    #[serde(default)]
+    pub libs: Vec<String>,
+}
+
+impl PackageFlags {
+    /// pkg-config is queried once for all packages, result is cached until packages or
+    /// their .pc files change, cache is checked without running pkg-config.
+    /// Every package (with its version) is checked first, so the missing one is reported.
+    pub fn query(packages: &[String], cache: &Path, save: bool) -> Result<Self, ParsingError> {
+        if packages.is_empty() {
+            return Ok(Self::default());
+        }
+        if let Some(cached) = fs::read_to_string(cache)
+            .ok()
+            .and_then(|s| toml::from_str::<Self>(&s).ok())
+            .filter(|c| c.key == Self::key_of(packages, &c.pc_dirs))
+        {
+            return Ok(cached);
+        }
+
+        Logger::info(&format!("Querying {PKG_CONFIG}: {}", packages.join(", ")));
+        for package in packages {
+            Self::run(package, &["--print-errors", "--exists", package])?;
+        }
+        let mut args: Vec<&str> = packages.iter().map(String::as_str).collect();
+        args.insert(0, "--cflags");
+        let cflags = Self::run(&packages.join(", "), &args)?;
+        args[0] = "--libs";
+        let libs = Self::run(&packages.join(", "), &args)?;
+
+        let pc_dirs = Self::search_dirs();
+        let res = Self {
+            key: Self::key_of(packages, &pc_dirs),
+            pc_dirs,
+            cflags: cflags.split_whitespace().map(String::from).collect(),
+            libs: libs.split_whitespace().map(String::from).collect(),
+        };
+        if !save {
+            return Ok(res);
+        }
+        if let Some(parent) = cache.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Cache is only an optimization.
+        if let Ok(str) = toml::to_string(&res) {
+            let _ = fs::write(cache, str);
+        }
+        Ok(res)
+    }
+
+    /// PKG_CONFIG_PATH followed by PKG_CONFIG_LIBDIR or default directories of pkg-config.
+    fn search_dirs() -> Vec<PathBuf> {
+        let mut res: Vec<PathBuf> = env::var_os("PKG_CONFIG_PATH")
+            .map(|p| env::split_paths(&p).collect())
+            .unwrap_or_default();
+        match env::var_os("PKG_CONFIG_LIBDIR") {
+            Some(dirs) => res.extend(env::split_paths(&dirs)),
+            None => {
+                let dirs = Self::run(PKG_CONFIG, &["--variable", "pc_path", PKG_CONFIG]);
+                res.extend(env::split_paths(dirs.unwrap_or_default().trim()));
+            }
+        }
+        res.retain(|d| !d.as_os_str().is_empty());
+        res
+    }
+
+    /// Missing .pc files are part of key too, so they are noticed once they appear.
+    fn key_of(packages: &[String], pc_dirs: &[PathBuf]) -> String {
+        let mut hasher = Sha256::new();
+        for package in packages {
+            hasher.update(package.as_bytes());
+            hasher.update([0]);
+        }
+        for var in [
+            "PKG_CONFIG_PATH",
+            "PKG_CONFIG_LIBDIR",
+            "PKG_CONFIG_SYSROOT_DIR",
+        ] {
+            hasher.update(env::var(var).unwrap_or_default().as_bytes());
+            hasher.update([0]);
+        }
+        for dir in pc_dirs {
+            hasher.update(dir.as_os_str().as_encoded_bytes());
+            hasher.update([0]);
+        }
+
+        let mut pending: Vec<String> = packages
+            .iter()
+            .filter_map(|p| p.split_whitespace().next())
+            .map(String::from)
+            .collect();
+        let mut seen = HashSet::new();
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            hasher.update(name.as_bytes());
+            hasher.update([0]);
+            let Some(pc) = Self::find_pc(&name, pc_dirs) else {
+                continue;
+            };
+            let Ok(content) = fs::read_to_string(&pc) else {
+                continue;
+            };
+            let modified = fs::metadata(&pc)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            hasher.update(pc.as_os_str().as_encoded_bytes());
+            hasher.update(modified.as_nanos().to_le_bytes());
+            hasher.update(content.as_bytes());
+            hasher.update([0]);
+            pending.extend(Self::required_by(&content));
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Package may be given by path of its .pc file, as pkg-config accepts it.
+    fn find_pc(name: &str, pc_dirs: &[PathBuf]) -> Option<PathBuf> {
+        if name.ends_with(".pc") {
+            return Some(PathBuf::from(name)).filter(|p| p.is_file());
+        }
+        pc_dirs
+            .iter()
+            .map(|d| d.join(format!("{name}.pc")))
+            .find(|p| p.is_file())
+    }
+
+    /// Names of packages in 'Requires' and 'Requires.private', without version constraints.
+    fn required_by(content: &str) -> Vec<String> {
+        let mut res = Vec::default();
+        for line in content.lines() {
+            let Some(list) = line
+                .strip_prefix("Requires:")
+                .or_else(|| line.strip_prefix("Requires.private:"))
+            else {
+                continue;
+            };
+            let mut tokens = list
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|t| !t.is_empty());
+            while let Some(token) = tokens.next() {
+                if matches!(token, "<" | "<=" | "=" | "!=" | ">=" | ">") {
+                    tokens.next();
+                } else {
+                    res.push(token.to_string());
+                }
+            }
+        }
+        res
+    }
+
+    /// Stdout of pkg-config, last line of its errors is reported as missing package.
+    fn run(package: &str, args: &[&str]) -> Result<String, ParsingError> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let output = ProcSpawner::spawn_and_wait(PKG_CONFIG, &args)
+            .map_err(|err| ParsingError::MissingPackage(package.into(), err.to_string()))?;
+        if output.exit_code != 0 {
+            let reason = output
+                .errs
+                .lines()
+                .rfind(|l| !l.trim().is_empty())
+                .map(|l| l.trim().to_string())
+                .unwrap_or(format!("{PKG_CONFIG} exited with {}", output.exit_code));
+            return Err(ParsingError::MissingPackage(package.into(), reason));
+        }
+        Ok(output.outs)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{env, fs, path::PathBuf, process::Command};
+
+    use super::{PackageFlags, PKG_CONFIG};
+    use crate::errors::ParsingError;
+
+    #[test]
+    fn packages_are_queried_and_cached() {
+        if Command::new(PKG_CONFIG).arg("--version").output().is_err() {
+            return;
+        }
+        let dir = env::temp_dir().join(format!("cum_pkg_config_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let pc = dir.join("cumtest.pc");
+        fs::write(
+            &pc,
+            "Name: cumtest\nDescription: test\nVersion: 2.30.1\nCflags: -I/opt/cumtest/include -DCUMTEST\nLibs: -L/opt/cumtest/lib -lcumtest\n",
+        )
+        .unwrap();
+        let cache = dir.join("target/pkg_config.toml");
+
+        let packages = vec![format!("{} >= 2.26", pc.display())];
+        // Nothing is written by dry run.
+        let flags = PackageFlags::query(&packages, &cache, false).unwrap();
+        assert!(!cache.exists());
+        assert_eq!(PackageFlags::query(&packages, &cache, true).unwrap(), flags);
+        assert_eq!(flags.cflags, ["-I/opt/cumtest/include", "-DCUMTEST"]);
+        assert_eq!(flags.libs, ["-L/opt/cumtest/lib", "-lcumtest"]);
+
+        // Cached result is used while packages and their .pc files stay the same.
+        assert_eq!(PackageFlags::query(&packages, &cache, true).unwrap(), flags);
+        let cached = fs::read_to_string(&cache).unwrap();
+        assert!(cached.contains("-lcumtest"));
+
+        // Changed .pc file is queried again.
+        fs::write(
+            &pc,
+            "Name: cumtest\nDescription: test\nVersion: 2.31.0\nCflags: -DCUMTEST_NEW\nLibs: -lcumtest2\n",
+        )
+        .unwrap();
+        let flags = PackageFlags::query(&packages, &cache, true).unwrap();
+        assert_eq!(flags.cflags, ["-DCUMTEST_NEW"]);
+        assert_eq!(flags.libs, ["-lcumtest2"]);
+
+        // Removed package is not resolved from cache.
+        fs::remove_file(&pc).unwrap();
+        assert!(matches!(
+            PackageFlags::query(&packages, &cache, true),
+            Err(ParsingError::MissingPackage(..))
+        ));
+
+        let packages = vec![format!("{} >= 3.0", pc.display())];
+        assert!(matches!(
+            PackageFlags::query(&packages, &cache, true),
+            Err(ParsingError::MissingPackage(..))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cache_key_follows_required_packages() {
+        let dir = env::temp_dir().join(format!("cum_pkg_config_key_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let pc = dir.join("cumtop.pc");
+        fs::write(&pc, "Name: cumtop\nRequires: cumdep >= 1.0, cumother\n").unwrap();
+        fs::write(dir.join("cumdep.pc"), "Name: cumdep\nCflags: -DOLD\n").unwrap();
+        assert_eq!(
+            PackageFlags::required_by("Requires: a >= 1.0, b\nRequires.private: c = 2 d\n"),
+            ["a", "b", "c", "d"]
+        );
+
+        let packages = vec![pc.display().to_string()];
+        let pc_dirs = vec![dir.clone()];
+        let key = PackageFlags::key_of(&packages, &pc_dirs);
+        assert_eq!(PackageFlags::key_of(&packages, &pc_dirs), key);
+
+        // Transitive package changed.
+        fs::write(dir.join("cumdep.pc"), "Name: cumdep\nCflags: -DNEW\n").unwrap();
+        let changed = PackageFlags::key_of(&packages, &pc_dirs);
+        assert_ne!(changed, key);
+
+        // Missing package appeared.
+        fs::write(dir.join("cumother.pc"), "Name: cumother\n").unwrap();
+        assert_ne!(PackageFlags::key_of(&packages, &pc_dirs), changed);
+        assert_ne!(
+            PackageFlags::key_of(&packages, &[PathBuf::from("/nonexistent")]),
+            key
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -11,6 +11,7 @@ pub type ProcSpawnRusult = Result<ProcOutput, ExecutionError>;
 
 pub struct ProcSpawner;
 
+#[derive(Default)]
 pub struct ProcOutput {
     pub outs: String,
     pub errs: String,
@@ -46,22 +47,29 @@ impl ProcSpawner {
         })
     }
 
-    pub fn spawn_into_pool(
+    /// Result is sent along with tag, to know which process it belongs to.
+    pub fn spawn_into_pool<T: Send + 'static>(
         exe: String,
         args: Vec<String>,
+        tag: T,
         tp: &ThreadPool,
-        tx: Sender<ProcSpawnRusult>,
+        tx: Sender<(T, ProcSpawnRusult)>,
     ) {
         tp.execute(move || {
             let res = Self::spawn_and_wait_owned(exe, args);
-            tx.send(res).unwrap();
+            tx.send((tag, res)).unwrap();
         });
     }
 
     /// Spawn process and inherit all stdio streams from parent. Returns exit_code.
-    pub fn spawn_into_parent(exe: &str, args: &[String]) -> Result<i32, ExecutionError> {
+    pub fn spawn_into_parent(
+        exe: &str,
+        args: &[String],
+        envs: &[(String, String)],
+    ) -> Result<i32, ExecutionError> {
         let mut handle = Command::new(exe)
             .args(args)
+            .envs(envs.iter().cloned())
             .stdin(Stdio::inherit())
             .stderr(Stdio::inherit())
             .stdout(Stdio::inherit())
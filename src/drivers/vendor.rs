@@ -0,0 +1,482 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::FetchError, logger::Logger, parsing::config::DependencyConfig};
+
+use super::{fs_manager::FilesystemManager, proc_spawner::ProcSpawner};
+
+pub const LOCK_FILE_PATH: &str = "Cum.lock";
+/// Fetched sources are kept in <cache>/vendor, their objects in <obj_dir>/vendor/<name>
+pub const VENDOR_DIR: &str = "vendor";
+
+/// Sources of git or tarball dependency ready to be compiled, pathes are absolute.
+#[derive(Debug, Default, Clone)]
+pub struct VendoredSource {
+    pub name: String,
+    pub root: PathBuf,
+    pub sources: Vec<PathBuf>,
+    pub include_dirs: Vec<PathBuf>,
+    pub cflags: Vec<String>,
+}
+
+/// Cum.lock: exact commit or checksum every fetched dependency is pinned to.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockFile {
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, LockedDependency>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedDependency {
+    /// 'git+<url>#<tag or rev>' or tarball url, pin is dropped once source changes.
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Fetches dependencies into cache (once) and verifies them on every build:
+/// git checkout has to be at locked commit without local changes, tarball has to match its sha256.
+/// With dry run nothing is fetched, dependencies missing from cache are only reported.
+pub struct Vendor {
+    dir: PathBuf,
+    locked: LockFile,
+    lock: LockFile,
+    dry_run: bool,
+}
+
+impl Vendor {
+    /// Missing lock file is treated as empty.
+    pub fn new(dir: PathBuf, lock_path: &Path, dry_run: bool) -> Result<Self, FetchError> {
+        let locked = match fs::read_to_string(lock_path) {
+            Ok(str) => toml::from_str(&str)?,
+            Err(_) => LockFile::default(),
+        };
+        Ok(Self {
+            dir,
+            locked,
+            lock: LockFile::default(),
+            dry_run,
+        })
+    }
+
+    pub fn fetch(
+        &mut self,
+        name: &str,
+        dependency: &DependencyConfig,
+    ) -> Result<VendoredSource, FetchError> {
+        let source = Self::source_of(dependency);
+        let locked = self
+            .locked
+            .dependencies
+            .get(name)
+            .filter(|l| l.source == source)
+            .cloned();
+
+        let (root, pin) = match (&dependency.git, &dependency.url, &dependency.sha256) {
+            (Some(url), _, _) => {
+                let reference = dependency.tag.as_ref().or(dependency.rev.as_ref());
+                let locked = locked.and_then(|l| l.commit);
+                let (root, commit) =
+                    self.fetch_git(name, url, reference.cloned().unwrap_or_default(), locked)?;
+                (root, (Some(commit), None))
+            }
+            (None, Some(url), Some(sha256)) => {
+                let sha256 = sha256.to_ascii_lowercase();
+                let root = self.fetch_tarball(name, url, &sha256)?;
+                (root, (None, Some(sha256)))
+            }
+            _ => return Err(FetchError::FetchFailed(name.into(), "no source".into())),
+        };
+        self.lock.dependencies.insert(
+            name.into(),
+            LockedDependency {
+                source,
+                commit: pin.0,
+                sha256: pin.1,
+            },
+        );
+
+        let or_default = |dirs: &[PathBuf], default: &str| -> Vec<PathBuf> {
+            if dirs.is_empty() {
+                vec![root.join(default)]
+            } else {
+                dirs.iter().map(|d| root.join(d)).collect()
+            }
+        };
+        Ok(VendoredSource {
+            name: name.into(),
+            sources: or_default(&dependency.sources, "src"),
+            include_dirs: or_default(&dependency.include_dirs, "include"),
+            cflags: dependency.cflags.clone(),
+            root,
+        })
+    }
+
+    /// Lock file of fetched dependencies only, written if it has changed.
+    pub fn save(&self, lock_path: &Path) -> Result<(), FetchError> {
+        if self.lock != self.locked {
+            fs::write(lock_path, toml::to_string(&self.lock)?)?;
+        }
+        Ok(())
+    }
+
+    fn source_of(dependency: &DependencyConfig) -> String {
+        match (&dependency.git, &dependency.url) {
+            (Some(git), _) => {
+                let reference = dependency.tag.as_ref().or(dependency.rev.as_ref());
+                format!("git+{git}#{}", reference.cloned().unwrap_or_default())
+            }
+            (None, Some(url)) => url.clone(),
+            _ => String::default(),
+        }
+    }
+
+    /// Checkout is kept in <cache>/vendor/git/<name>-<commit>, repository is cloned
+    /// only if there is no checkout of locked commit yet. Tag or rev has to resolve
+    /// to locked commit, so moved tag is not followed.
+    fn fetch_git(
+        &self,
+        name: &str,
+        url: &str,
+        reference: String,
+        locked: Option<String>,
+    ) -> Result<(PathBuf, String), FetchError> {
+        let git_dir = self.dir.join("git");
+        if let Some(commit) = &locked {
+            let root = git_dir.join(format!("{name}-{commit}"));
+            if root.exists() {
+                Self::verify_checkout(name, &root, commit)?;
+                return Ok((root, commit.clone()));
+            }
+        }
+
+        let wanted = match locked.clone() {
+            Some(locked) if reference.is_empty() => locked,
+            _ if reference.is_empty() => "HEAD".into(),
+            _ => reference,
+        };
+        if self.dry_run {
+            Logger::info(&format!("Fetch: {name} from {url} at {wanted}"));
+            let commit = locked.unwrap_or(wanted);
+            return Ok((git_dir.join(format!("{name}-{commit}")), commit));
+        }
+
+        Logger::info(&format!("Fetching {name} from {url}"));
+        let tmp = self.tmp_dir(name)?;
+        let checkout = || -> Result<String, FetchError> {
+            Self::git(name, &["clone", "--quiet", url, &tmp.display().to_string()])?;
+            Self::git_in(name, &tmp, &["checkout", "--quiet", "--detach", &wanted])?;
+            let commit = Self::git_in(name, &tmp, &["rev-parse", "HEAD"])?;
+            match locked.filter(|l| *l != commit) {
+                Some(locked) => Err(FetchError::LockMismatch {
+                    name: name.into(),
+                    expected: locked,
+                    actual: commit,
+                }),
+                None => Ok(commit),
+            }
+        };
+        let commit = Self::removed_on_error(&tmp, checkout())?;
+
+        let root = git_dir.join(format!("{name}-{commit}"));
+        Self::move_into_place(&tmp, &root)?;
+        Self::verify_checkout(name, &root, &commit)?;
+        Ok((root, commit))
+    }
+
+    /// Checkout has to stay at its commit, sources are not edited in cache.
+    fn verify_checkout(name: &str, root: &Path, commit: &str) -> Result<(), FetchError> {
+        let head = Self::git_in(name, root, &["rev-parse", "HEAD"])?;
+        let changes = Self::git_in(name, root, &["status", "--porcelain"])?;
+        if head != commit || !changes.is_empty() {
+            return Err(FetchError::LockMismatch {
+                name: name.into(),
+                expected: commit.into(),
+                actual: if changes.is_empty() {
+                    head
+                } else {
+                    format!("{head} with local changes")
+                },
+            });
+        }
+        Ok(())
+    }
+
+    /// Archive is kept as <cache>/vendor/tarballs/<sha256> and verified on every build,
+    /// it is unpacked into <cache>/vendor/src/<name>-<sha256>, single top directory is stripped.
+    fn fetch_tarball(&self, name: &str, url: &str, sha256: &str) -> Result<PathBuf, FetchError> {
+        let archive = self.dir.join("tarballs").join(sha256);
+        let root = self.dir.join("src").join(format!("{name}-{sha256}"));
+        if self.dry_run && !(archive.exists() && root.exists()) {
+            Logger::info(&format!("Fetch: {name} from {url}"));
+            return Ok(root);
+        }
+        if !archive.exists() {
+            Logger::info(&format!("Fetching {name} from {url}"));
+            let tmp = self.tmp_dir(name)?;
+            let download = || -> Result<(), FetchError> {
+                let file = tmp.join("archive");
+                match url.strip_prefix("file://") {
+                    Some(path) => {
+                        fs::copy(path, &file)?;
+                    }
+                    None if Path::new(url).exists() => {
+                        fs::copy(url, &file)?;
+                    }
+                    None => {
+                        let file_str = file.display().to_string();
+                        Self::run(name, "curl", &["-sSfL", "-o", &file_str, url])?;
+                    }
+                }
+                Self::verify_checksum(name, &file, sha256)?;
+                if let Some(parent) = archive.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(&file, &archive)?;
+                Ok(())
+            };
+            Self::removed_on_error(&tmp, download())?;
+            fs::remove_dir_all(&tmp)?;
+        }
+        Self::verify_checksum(name, &archive, sha256)?;
+
+        if !root.exists() {
+            let tmp = self.tmp_dir(name)?;
+            let unpack = || -> Result<(), FetchError> {
+                let archive_str = archive.display().to_string();
+                let tmp_str = tmp.display().to_string();
+                Self::run(name, "tar", &["-xf", &archive_str, "-C", &tmp_str])?;
+
+                let entries: Vec<PathBuf> = fs::read_dir(&tmp)?
+                    .filter_map(Result::ok)
+                    .map(|e| e.path())
+                    .collect();
+                match entries.as_slice() {
+                    [top] if top.is_dir() => {
+                        Self::move_into_place(top, &root)?;
+                        fs::remove_dir_all(&tmp)?;
+                    }
+                    _ => Self::move_into_place(&tmp, &root)?,
+                }
+                Ok(())
+            };
+            Self::removed_on_error(&tmp, unpack())?;
+        }
+        Ok(root)
+    }
+
+    /// Hex digest is compared case insensitively.
+    fn verify_checksum(name: &str, file: &Path, expected: &str) -> Result<(), FetchError> {
+        let actual = FilesystemManager::hash_file(file).unwrap_or_default();
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(FetchError::ChecksumMismatch {
+                name: name.into(),
+                expected: expected.into(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Fresh directory inside of cache, so that rename into place never crosses filesystems.
+    fn tmp_dir(&self, name: &str) -> Result<PathBuf, FetchError> {
+        let dir = self
+            .dir
+            .join("tmp")
+            .join(format!("{name}-{}", process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// Failed fetch leaves nothing behind in cache.
+    fn removed_on_error<T>(tmp: &Path, res: Result<T, FetchError>) -> Result<T, FetchError> {
+        if res.is_err() && tmp.exists() {
+            let _ = fs::remove_dir_all(tmp);
+        }
+        res
+    }
+
+    /// Another build may have fetched the same sources meanwhile, its copy is kept.
+    fn move_into_place(from: &Path, to: &Path) -> Result<(), FetchError> {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if to.exists() {
+            fs::remove_dir_all(from)?;
+        } else {
+            fs::rename(from, to)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn git(name: &str, args: &[&str]) -> Result<String, FetchError> {
+        Self::run(name, "git", args)
+    }
+
+    #[inline]
+    fn git_in(name: &str, dir: &Path, args: &[&str]) -> Result<String, FetchError> {
+        let dir = dir.display().to_string();
+        let args: Vec<&str> = ["-C", dir.as_str()]
+            .into_iter()
+            .chain(args.iter().copied())
+            .collect();
+        Self::run(name, "git", &args)
+    }
+
+    /// Trimmed stdout of successful process.
+    fn run(name: &str, exe: &str, args: &[&str]) -> Result<String, FetchError> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let output = ProcSpawner::spawn_and_wait(exe, &args)?;
+        if output.exit_code != 0 {
+            return Err(FetchError::FetchFailed(
+                name.into(),
+                output.errs.trim().to_string(),
+            ));
+        }
+        Ok(output.outs.trim().to_string())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{env, fs, path::Path, process::Command};
+
+    use sha2::{Digest, Sha256};
+
+    use super::{LockFile, Vendor};
+    use crate::{errors::FetchError, parsing::config::DependencyConfig};
+
+    #[test]
+    fn tarball_is_verified_and_locked() {
+        let dir = env::temp_dir().join(format!("cum_vendor_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("pkg/lib-1.0/src")).unwrap();
+        fs::write(
+            dir.join("pkg/lib-1.0/src/lib.c"),
+            "int lib(void) { return 1; }\n",
+        )
+        .unwrap();
+        let archive = dir.join("lib.tar");
+        let status = Command::new("tar")
+            .args(["-cf", archive.to_str().unwrap(), "-C"])
+            .arg(dir.join("pkg"))
+            .arg("lib-1.0")
+            .status();
+        if !status.is_ok_and(|s| s.success()) {
+            return;
+        }
+        let sha256 = format!("{:x}", Sha256::digest(fs::read(&archive).unwrap()));
+        let lock_path = dir.join("Cum.lock");
+
+        let mut vendor = Vendor::new(dir.join("cache"), &lock_path, false).unwrap();
+        let mut dependency = DependencyConfig {
+            url: Some(format!("file://{}", archive.display())),
+            sha256: Some("0".repeat(64)),
+            ..Default::default()
+        };
+        assert!(matches!(
+            vendor.fetch("lib", &dependency),
+            Err(FetchError::ChecksumMismatch { .. })
+        ));
+        let tmp = dir.join("cache/tmp");
+        assert_eq!(fs::read_dir(&tmp).unwrap().count(), 0);
+
+        // Dry run reports missing archive without fetching it.
+        dependency.sha256 = Some(sha256.to_uppercase());
+        let mut dry_vendor = Vendor::new(dir.join("cache"), &lock_path, true).unwrap();
+        let source = dry_vendor.fetch("lib", &dependency).unwrap();
+        assert!(!source.root.exists());
+        assert!(!dir.join("cache/tarballs").exists());
+
+        let source = vendor.fetch("lib", &dependency).unwrap();
+        assert!(source.root.join("src/lib.c").exists());
+        assert_eq!(source.sources, vec![source.root.join("src")]);
+        vendor.save(&lock_path).unwrap();
+
+        let lock: LockFile = toml::from_str(&fs::read_to_string(&lock_path).unwrap()).unwrap();
+        assert_eq!(lock.dependencies["lib"].sha256, Some(sha256));
+        assert_eq!(lock.dependencies["lib"].source, dependency.url.unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn git(dir: &Path, args: &[&str]) -> bool {
+        Command::new("git")
+            .args([
+                "-c",
+                "user.name=cum",
+                "-c",
+                "user.email=cum@localhost",
+                "-C",
+            ])
+            .arg(dir)
+            .args(args)
+            .output()
+            .is_ok_and(|o| o.status.success())
+    }
+
+    #[test]
+    fn moved_tag_does_not_match_lock() {
+        let dir = env::temp_dir().join(format!("cum_vendor_git_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repo = dir.join("repo");
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::write(repo.join("src/lib.c"), "int lib(void) { return 1; }\n").unwrap();
+        if !git(&repo, &["init", "--quiet"]) {
+            return;
+        }
+        assert!(git(&repo, &["add", "-A"]));
+        assert!(git(&repo, &["commit", "--quiet", "-m", "first"]));
+        assert!(git(&repo, &["tag", "v1"]));
+        let lock_path = dir.join("Cum.lock");
+        let dependency = DependencyConfig {
+            git: Some(format!("file://{}", repo.display())),
+            tag: Some("v1".into()),
+            ..Default::default()
+        };
+
+        let mut vendor = Vendor::new(dir.join("cache"), &lock_path, false).unwrap();
+        let source = vendor.fetch("lib", &dependency).unwrap();
+        assert!(source.root.join("src/lib.c").exists());
+        vendor.save(&lock_path).unwrap();
+
+        fs::write(repo.join("src/lib.c"), "int lib(void) { return 2; }\n").unwrap();
+        assert!(git(&repo, &["commit", "--quiet", "-am", "second"]));
+        assert!(git(&repo, &["tag", "-f", "v1"]));
+
+        // Fresh cache has no checkout of locked commit, so tag is fetched again.
+        let mut vendor = Vendor::new(dir.join("fresh_cache"), &lock_path, false).unwrap();
+        assert!(matches!(
+            vendor.fetch("lib", &dependency),
+            Err(FetchError::LockMismatch { .. })
+        ));
+        let tmp = dir.join("fresh_cache/tmp");
+        assert_eq!(fs::read_dir(&tmp).unwrap().count(), 0);
+        assert!(!dir.join("fresh_cache/git").exists());
+
+        // Tag is followed once its entry is removed from lock.
+        let mut lock: LockFile = toml::from_str(&fs::read_to_string(&lock_path).unwrap()).unwrap();
+        let old = lock.dependencies.remove("lib").unwrap().commit;
+        fs::write(&lock_path, toml::to_string(&lock).unwrap()).unwrap();
+        let mut vendor = Vendor::new(dir.join("fresh_cache"), &lock_path, false).unwrap();
+        let source = vendor.fetch("lib", &dependency).unwrap();
+        vendor.save(&lock_path).unwrap();
+        let lock: LockFile = toml::from_str(&fs::read_to_string(&lock_path).unwrap()).unwrap();
+        assert_ne!(lock.dependencies["lib"].commit, old);
+        assert!(fs::read_to_string(source.root.join("src/lib.c"))
+            .unwrap()
+            .contains("return 2"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -11,6 +11,53 @@ pub enum ParsingError {
     #[error("Unallowed standart: '{0}'")]
     WrongStandart(u8),
 
+    #[error("Unknown language in 'lang_cflags': '{0}'")]
+    UnknownLanguage(String),
+
+    #[error("Invalid exclude pattern: [{0}]")]
+    InvalidGlob(String),
+
+    #[error("Target '{0}' is declared twice")]
+    DuplicateTarget(String),
+
+    #[error("Target can't be named '{0}', the name is reserved for object directory")]
+    ReservedTarget(String),
+
+    #[error("Target '{0}' required by '{1}' is not declared")]
+    UnknownTarget(String, String),
+
+    #[error("Executable '{0}' can't be a dependency of '{1}'")]
+    ExecutableDependency(String, String),
+
+    #[error("Targets depend on each other: {0}")]
+    TargetCycle(String),
+
+    #[error("Workspace member '{0}' doesn't exist")]
+    MissingMember(PathBuf),
+
+    #[error("Workspace member '{0}' is listed twice")]
+    DuplicateMember(String),
+
+    #[error("Workspace member '{0}' is not listed in members")]
+    UnknownMember(String),
+
+    #[error("Workspace members depend on each other: {0}")]
+    MemberCycle(String),
+
+    #[error(
+        "Dependency '{0}' needs one of 'path', 'git' with 'tag' or 'rev', 'url' with 'sha256'"
+    )]
+    InvalidDependency(String),
+
+    #[error("Dependency '{0}' is not cum project: '{1}' has no Cum.toml")]
+    MissingDependency(String, PathBuf),
+
+    #[error("Dependency '{0}' depends on its dependent")]
+    DependencyCycle(String),
+
+    #[error("Package '{0}' is not found by pkg-config: {1}")]
+    MissingPackage(String, String),
+
     #[error("Parameter required: '{0}'")]
     ParamRequired(String),
 
@@ -30,6 +77,15 @@ pub enum QueryError {
     #[error("Invalid entry point: '{0}'")]
     InvalidEntryPoint(PathBuf),
 
+    #[error("Invalid target: '{0}'")]
+    InvalidTarget(String),
+
+    #[error("Invalid workspace member: '{0}'")]
+    InvalidMember(String),
+
+    #[error("'--lib' can't be used with declared targets, declare library target instead")]
+    LibWithTargets,
+
     #[error("No arguments provided")]
     NoArgs,
 }
@@ -42,6 +98,63 @@ pub enum PlannerError {
 
     #[error("Execution error: [{0}]")]
     ExecutionError(#[from] ExecutionError),
+
+    #[error("Parsing error: [{0}]")]
+    ParsingError(#[from] ParsingError),
+
+    #[error("Module '{0}' imported by '{1}' is not found")]
+    UnknownModule(String, PathBuf),
+
+    #[error("Module '{0}' is exported by both '{1}' and '{2}'")]
+    DuplicateModule(String, PathBuf, PathBuf),
+
+    #[error("Modules import each other: {0}")]
+    ModuleCycle(String),
+
+    #[error("Directory '{0}' is not inside of project, it is not removed")]
+    OutsideOfProject(PathBuf),
+
+    #[error("Package file '{0}' doesn't exist")]
+    MissingPackageFile(PathBuf),
+
+    #[error("Packages are archived by GNU tar, '{0}' is not one")]
+    NotGnuTar(String),
+}
+
+// Fetch error
+#[derive(Debug, Error)]
+pub enum FetchError {
+    #[error("Execution error: [{0}]")]
+    ExecutionError(#[from] ExecutionError),
+
+    #[error("Filesystem error: [{0}]")]
+    FileIO(#[from] std::io::Error),
+
+    #[error("Toml parsing error: [{0}]")]
+    TomlParsing(#[from] toml::de::Error),
+
+    #[error("Toml serialization error: [{0}]")]
+    TomlSerialization(#[from] toml::ser::Error),
+
+    #[error("No cache directory for fetched dependencies, set CUM_CACHE_DIR")]
+    NoCacheDir,
+
+    #[error("Fetching '{0}' failed: {1}")]
+    FetchFailed(String, String),
+
+    #[error("Checksum of '{name}' doesn't match: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Sources of '{name}' don't match Cum.lock: expected {expected}, got {actual}")]
+    LockMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
 }
 
 // Execution Error
@@ -52,4 +165,7 @@ pub enum ExecutionError {
 
     #[error("Process finished with error ({code}): {errs}")]
     ProcErr { code: i32, errs: String },
+
+    #[error("Toml serialization error: [{0}]")]
+    TomlSerialization(#[from] toml::ser::Error),
 }
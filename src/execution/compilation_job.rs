@@ -0,0 +1,124 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::drivers::{
+    fs_manager::FilesystemManager,
+    language::Language,
+    object_cache::ObjectCache,
+    proc_spawner::{ProcSpawnRusult, ProcSpawner},
+};
+
+/// Single compiler call running on thread pool, object cache is consulted first.
+pub struct CompilationJob {
+    pub exe: String,
+    pub args: Vec<String>,
+    pub output: PathBuf,
+    pub compiler_version: String,
+    pub cache: Option<ObjectCache>,
+}
+
+pub struct CompilationOutcome {
+    pub res: ProcSpawnRusult,
+    /// None if object cache wasn't consulted.
+    pub cache_hit: Option<bool>,
+}
+
+impl CompilationJob {
+    pub fn run(self) -> CompilationOutcome {
+        let key = self.cache.as_ref().and_then(|_| self.cache_key());
+        let (Some(cache), Some(key)) = (&self.cache, key) else {
+            return CompilationOutcome {
+                res: ProcSpawner::spawn_and_wait(&self.exe, &self.args),
+                cache_hit: None,
+            };
+        };
+
+        // Plain assembly is compiled without depfile.
+        let depfile = self
+            .args
+            .iter()
+            .any(|a| a == "-MMD")
+            .then(|| FilesystemManager::obj_to_dep(&self.output));
+        if cache.fetch(&key, &self.output, depfile.as_deref()) {
+            return CompilationOutcome {
+                res: Ok(Default::default()),
+                cache_hit: Some(true),
+            };
+        }
+
+        let res = ProcSpawner::spawn_and_wait(&self.exe, &self.args);
+        if matches!(&res, Ok(o) if o.exit_code == 0) {
+            // Failed store only means next build won't hit the cache.
+            let _ = cache.store(&key, &self.output, depfile.as_deref());
+        }
+        CompilationOutcome {
+            res,
+            cache_hit: Some(false),
+        }
+    }
+
+    /// Preprocessed source along with command line without output pathes.
+    /// None if source can't be preprocessed, compiler will report the error itself.
+    fn cache_key(&self) -> Option<String> {
+        let mut preprocessor_args = Vec::default();
+        let mut key_args = Vec::default();
+        let mut source = None;
+        let mut args = self.args.iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "-MF" => {
+                    args.next();
+                }
+                "-MMD" => {}
+                "-c" => {
+                    let src = args.next()?;
+                    preprocessor_args.extend_from_slice(&["-E".into(), src.clone()]);
+                    key_args.push(src.clone());
+                    source = Some(Path::new(src));
+                }
+                // Preprocessed source doesn't contain precompiled header.
+                "-include-pch" => {
+                    let pch = args.next()?;
+                    preprocessor_args.extend_from_slice(&[arg.clone(), pch.clone()]);
+                    key_args.push(FilesystemManager::hash_file(Path::new(pch))?);
+                }
+                // Same goes for imported modules.
+                _ if arg.starts_with("-fmodule-file=") => {
+                    let (name, bmi) = arg.rsplit_once('=')?;
+                    preprocessor_args.push(arg.clone());
+                    key_args.push(name.to_string());
+                    key_args.push(FilesystemManager::hash_file(Path::new(bmi))?);
+                }
+                _ => {
+                    preprocessor_args.push(arg.clone());
+                    key_args.push(arg.clone());
+                }
+            }
+        }
+
+        // Plain assembly isn't preprocessed, its content is used as is.
+        let preprocessed = match source {
+            Some(src) if !Language::of(src).is_preprocessed() => fs::read(src).ok()?,
+            _ => {
+                let res = ProcSpawner::spawn_and_wait(&self.exe, &preprocessor_args).ok()?;
+                if res.exit_code != 0 {
+                    return None;
+                }
+                res.outs.into_bytes()
+            }
+        };
+        // Debug info keeps absolute compilation directory.
+        if key_args.iter().any(|a| a.starts_with("-g")) {
+            key_args.push(env::current_dir().ok()?.display().to_string());
+        }
+
+        Some(ObjectCache::key(
+            &self.compiler_version,
+            &key_args,
+            &preprocessed,
+        ))
+    }
+}
@@ -1,27 +1,58 @@
+pub mod compilation_job;
+pub mod plan_printer;
+
 use std::{
     cell::RefCell,
+    collections::{HashMap, HashSet},
+    env,
+    path::{Path, PathBuf},
     sync::mpsc::{self, Receiver, Sender},
 };
 
 use path_clean::PathClean;
 
+use compilation_job::{CompilationJob, CompilationOutcome};
+
 use crate::{
     concurrency::timer::Timer,
     core::{Context, DiagnosticsCell, FilesystemManagerCell},
-    drivers::proc_spawner::{ProcSpawnRusult, ProcSpawner},
+    drivers::{
+        build_database::{BuildDatabase, ObjectRecord, BUILD_DB_PATH},
+        dependency_analyzer::MakefileParser,
+        fs_manager::FilesystemManager,
+        language::Language,
+        module_graph::ModuleGraph,
+        object_cache::ObjectCache,
+        proc_spawner::{ProcSpawnRusult, ProcSpawner},
+    },
     logger::Logger,
-    meta::COMPILER,
-    planning::{plan::PlanVisitor, step::Step},
+    meta::{COMPILER, TAR},
+    parsing::config::TargetKind,
+    planning::{build_target::BuildTarget, plan::PlanVisitor, step::Step},
 };
 
 pub struct PlanExecutor<'a> {
     ctx: &'a Context,
     fs_m: FilesystemManagerCell,
     diagnostics: DiagnosticsCell,
+    build_db: RefCell<BuildDatabase>,
+    object_cache: Option<ObjectCache>,
 
     compilation_timer: Timer,
-    compilation_tx: RefCell<Option<Sender<ProcSpawnRusult>>>,
-    compilation_rx: Receiver<ProcSpawnRusult>,
+    compilation_tx: Sender<(CompilationTag, CompilationOutcome)>,
+    compilation_rx: Receiver<(CompilationTag, CompilationOutcome)>,
+    /// BMIs being precompiled right now, their importers have to wait for them.
+    pending_modules: RefCell<HashSet<PathBuf>>,
+}
+
+/// Identifies compilation result, to record it into build database.
+struct CompilationTag {
+    source: PathBuf,
+    output: PathBuf,
+    fingerprint: String,
+    /// Precompiled header and BMIs object was compiled with,
+    /// their inputs are inputs of the object too.
+    imports: Vec<PathBuf>,
 }
 
 impl<'a> PlanExecutor<'a> {
@@ -36,9 +67,12 @@ impl<'a> PlanExecutor<'a> {
             ctx,
             fs_m,
             diagnostics,
+            build_db: ctx.build_db.clone().into(),
+            object_cache: ObjectCache::from_config(&ctx.config.cache),
             compilation_timer: Timer::default(),
             compilation_rx,
-            compilation_tx: Some(compilation_tx).into(),
+            compilation_tx,
+            pending_modules: RefCell::default(),
         }
     }
 
@@ -46,183 +80,665 @@ impl<'a> PlanExecutor<'a> {
         for step in self.ctx.plan.steps() {
             step.accept(self);
             if self.diagnostics.borrow().contains_error() {
-                return;
+                break;
             }
         }
+        self.save_build_db();
+        self.evict_object_cache();
+    }
+
+    fn evict_object_cache(&self) {
+        let removed = self.object_cache.as_ref().map(|c| c.evict()).unwrap_or(0);
+        if removed > 0 {
+            Logger::info(&format!("Object cache: {removed} old entries evicted"));
+        }
+    }
+
+    /// Wait until BMIs being precompiled are ready, if some of them is imported.
+    /// Returns false if compilation failed.
+    fn wait_for_imports(&self, imports: &[PathBuf]) -> bool {
+        let waits = {
+            let pending = self.pending_modules.borrow();
+            imports.iter().any(|i| pending.contains(i))
+        };
+        !waits || self.accept_compilation()
+    }
+
+    /// Run compiler on thread pool.
+    fn dispatch(&self, job: CompilationJob, source: &Path, imports: Vec<PathBuf>) {
+        self.compilation_timer.start();
+        let tag = CompilationTag {
+            source: source.into(),
+            output: job.output.clone(),
+            fingerprint: BuildDatabase::fingerprint(&self.ctx.compiler_version, &job.args),
+            imports,
+        };
+        let tx = self.compilation_tx.clone();
+        self.ctx
+            .thread_pool
+            .execute(move || tx.send((tag, job.run())).unwrap());
+    }
+
+    fn save_build_db(&self) {
+        if let Err(err) = self.build_db.borrow().save(Path::new(BUILD_DB_PATH)) {
+            self.diagnostics
+                .borrow_mut()
+                .report_warning(format!("Build database was not saved: {err}"));
+        }
     }
 
     /// Returns false if compilation failed.
     fn accept_compilation(&self) -> bool {
-        // Wait for all compilers to finish
+        // Wait for all compilers to finish, every result is already sent after that.
         self.ctx.thread_pool.join();
-        // Calls destructor on tx
-        *self.compilation_tx.borrow_mut() = None;
+        self.pending_modules.borrow_mut().clear();
         let mut success = true;
+        let mut hashes = HashMap::default();
+        let (mut total, mut cache_hits) = (0, 0);
 
+        let results: Vec<_> = self.compilation_rx.try_iter().collect();
+        if results.is_empty() {
+            return true;
+        }
         self.compilation_timer.stop();
         if let Some(time) = self.compilation_timer.elapsed_float() {
             Logger::info(&format!("Compilation finished at {:.2}", time));
         }
 
-        for res in self.compilation_rx.iter() {
-            match res {
+        for (tag, outcome) in results {
+            if let Some(hit) = outcome.cache_hit {
+                total += 1;
+                cache_hits += hit as usize;
+            }
+            match outcome.res {
                 Ok(o) if o.exit_code != 0 => {
                     success = false;
                     self.diagnostics
                         .borrow_mut()
                         .report_error_str(o.errs.trim().to_string());
                 }
-                Ok(o) if !o.errs.is_empty() => {
-                    self.diagnostics
-                        .borrow_mut()
-                        .report_warning(o.errs.trim().to_string());
+                Ok(o) => {
+                    if !o.errs.is_empty() {
+                        self.diagnostics
+                            .borrow_mut()
+                            .report_warning(o.errs.trim().to_string());
+                    }
+                    self.record_compilation(tag, &mut hashes);
                 }
                 Err(err) => {
                     success = false;
                     self.diagnostics.borrow_mut().report_error(err);
                 }
-                _ => {}
             }
         }
 
+        if total > 0 {
+            Logger::info(&format!(
+                "Object cache: {cache_hits} of {total} objects reused"
+            ));
+        }
         success
     }
 
-    fn accept_linkage(&self, res: ProcSpawnRusult) {
+    /// Inputs are taken from depfile written by compiler (plain assembly has none),
+    /// inputs of precompiled header and imported modules are added to them.
+    fn record_compilation(&self, tag: CompilationTag, hashes: &mut HashMap<PathBuf, String>) {
+        let mut inputs =
+            MakefileParser::read_depfile_of(&tag.output).unwrap_or_else(|| vec![tag.source]);
+        for import in &tag.imports {
+            inputs.extend(MakefileParser::read_depfile_of(import).unwrap_or_default());
+        }
+
+        if let Some(record) = ObjectRecord::from_inputs(&inputs, tag.fingerprint, hashes) {
+            self.build_db.borrow_mut().insert(&tag.output, record);
+        }
+    }
+
+    /// Result of compiler call made on main thread, returns false if it failed.
+    fn accept_blocking(&self, res: ProcSpawnRusult) -> bool {
         match res {
             Ok(o) if o.exit_code != 0 => {
                 self.diagnostics
                     .borrow_mut()
                     .report_error_str(o.errs.trim().to_string());
+                false
             }
             Ok(o) if !o.errs.is_empty() => {
                 self.diagnostics
                     .borrow_mut()
                     .report_warning(o.errs.trim().to_string());
+                true
             }
             Err(err) => {
                 self.diagnostics.borrow_mut().report_error(err);
+                false
             }
-            _ => {}
+            _ => true,
         }
     }
 
-    /// flags are ordered this way: <std> <cflags> <-I...> <-c file.cpp> <-o file.o>
+    /// Precompiled header of target, None if project has no pch or target is vendored.
+    #[inline]
+    pub fn pch_output(ctx: &Context, target: &str) -> Option<PathBuf> {
+        let obj_dir = &ctx.targets.get(target).filter(|t| !t.vendored)?.obj_dir;
+        ctx.config
+            .pch
+            .as_ref()
+            .map(|h| FilesystemManager::src_to_pch(h, obj_dir))
+    }
+
+    /// Precompiled header (only C++ can use it) and BMIs of modules source imports.
+    pub fn imported_outputs(ctx: &Context, source: &Path, target: &str) -> Vec<PathBuf> {
+        let Some(build_target) = ctx.targets.get(target) else {
+            return vec![];
+        };
+        Self::pch_output(ctx, target)
+            .filter(|_| Language::of(source) == Language::Cpp)
+            .into_iter()
+            .chain(
+                build_target
+                    .modules
+                    .imports_of(source)
+                    .iter()
+                    .map(|name| FilesystemManager::module_to_bmi(name, &build_target.obj_dir)),
+            )
+            .collect()
+    }
+
+    /// Inputs of precompiled header and imported modules, taken from their depfiles.
+    pub fn imported_inputs(ctx: &Context, source: &Path, target: &str) -> Vec<PathBuf> {
+        Self::imported_outputs(ctx, source, target)
+            .iter()
+            .filter_map(|o| MakefileParser::read_depfile_of(o))
+            .flatten()
+            .collect()
+    }
+
+    /// <-fmodule-file=name=file.pcm...> for every module source imports.
+    fn module_args(target: &BuildTarget, source: &Path) -> Vec<String> {
+        target
+            .modules
+            .imports_of(source)
+            .iter()
+            .map(|name| {
+                let bmi = FilesystemManager::module_to_bmi(name, &target.obj_dir);
+                format!("-fmodule-file={name}={}", bmi.clean().display())
+            })
+            .collect()
+    }
+
+    /// flags are ordered this way: <std> <cflags> <lang_cflags> <target cflags> <-I...> <pkg-config cflags> <-include-pch file.pch> <-fmodule-file=...> <-x c++-module> <-c file.cpp> <-o file.o> <-MMD -MF file.d>
+    /// std, lang_cflags and precompiled header depend on language of source, assembly has no depfile.
     #[inline]
-    fn full_cargs(&self, step: &Step) -> Option<Vec<String>> {
+    pub fn full_cargs(ctx: &Context, step: &Step) -> Option<Vec<String>> {
         let Step::Compilation {
             source,
             output,
             preset,
+            target: target_name,
         } = step
         else {
             return None;
         };
         let mut res = Vec::default();
-        let preset = self.ctx.config.presets.get(preset).unwrap();
+        let preset = ctx.config.presets.get(preset).unwrap();
+        let target = ctx.targets.get(target_name)?;
+        let language = Language::of(source);
 
         // <std>
-        res.push(format!("-std=c++{}", self.ctx.config.std_as_str().unwrap()));
+        res.extend(language.std_flag(&ctx.config));
         // <cflags>
         res.extend_from_slice(&preset.cflags);
+        // <lang_cflags>
+        if let Some(flags) = preset.lang_cflags.get(language.name()) {
+            res.extend_from_slice(flags);
+        }
+        // <target cflags>
+        res.extend_from_slice(&target.cflags);
         // <-I...>
-        self.ctx
-            .config
+        ctx.config
             .include_dirs
             .iter()
             .filter_map(|p| p.to_str())
             .for_each(|s| res.push(format!("-I{s}")));
+        // <pkg-config cflags>
+        res.extend_from_slice(&ctx.packages.cflags);
+        // <-include-pch file.pch>
+        if let Some(pch) = Self::pch_output(ctx, target_name).filter(|_| language == Language::Cpp)
+        {
+            res.extend_from_slice(&["-include-pch".into(), pch.clean().display().to_string()]);
+        }
+        // <-fmodule-file=...>
+        res.extend(Self::module_args(target, source));
+        // <-x c++-module>
+        if ModuleGraph::is_interface_unit(source) {
+            res.extend_from_slice(&["-x".into(), "c++-module".into()]);
+        }
         // <-c file.cpp>
         res.extend_from_slice(&["-c".into(), source.clean().display().to_string()]);
         // <-o file.o>
         res.extend_from_slice(&["-o".into(), output.clean().display().to_string()]);
+        // <-MMD -MF file.d>
+        if language.is_preprocessed() {
+            let depfile = FilesystemManager::obj_to_dep(output);
+            res.extend_from_slice(&[
+                "-MMD".into(),
+                "-MF".into(),
+                depfile.clean().display().to_string(),
+            ]);
+        }
 
         Some(res)
     }
 
-    /// flags are ordered this way: <std> <file.o...> <lflags> <-L...> <-l...> <-o file.exe>
+    /// flags are ordered this way: <std> <cflags> <lang_cflags> <target cflags> <-I...> <pkg-config cflags> <-include-pch file.pch> <-fmodule-file=...> <-x c++-module file.cppm> <--precompile> <-o file.pcm> <-MMD -MF file.d>
     #[inline]
-    fn full_largs(&self, step: &Step) -> Option<Vec<String>> {
-        let Step::Linkage {
+    pub fn full_bmi_args(ctx: &Context, step: &Step) -> Option<Vec<String>> {
+        let Step::ModuleInterface {
             source,
             output,
             preset,
+            target: target_name,
+        } = step
+        else {
+            return None;
+        };
+        let mut res = Vec::default();
+        let preset = ctx.config.presets.get(preset).unwrap();
+        let target = ctx.targets.get(target_name)?;
+
+        // <std>
+        res.push(format!("-std=c++{}", ctx.config.std_as_str().unwrap()));
+        // <cflags>
+        res.extend_from_slice(&preset.cflags);
+        // <lang_cflags>
+        if let Some(flags) = preset.lang_cflags.get(Language::Cpp.name()) {
+            res.extend_from_slice(flags);
+        }
+        // <target cflags>
+        res.extend_from_slice(&target.cflags);
+        // <-I...>
+        ctx.config
+            .include_dirs
+            .iter()
+            .filter_map(|p| p.to_str())
+            .for_each(|s| res.push(format!("-I{s}")));
+        // <pkg-config cflags>
+        res.extend_from_slice(&ctx.packages.cflags);
+        // <-include-pch file.pch>
+        if let Some(pch) = Self::pch_output(ctx, target_name) {
+            res.extend_from_slice(&["-include-pch".into(), pch.clean().display().to_string()]);
+        }
+        // <-fmodule-file=...>
+        res.extend(Self::module_args(target, source));
+        // <-x c++-module file.cppm>
+        res.extend_from_slice(&[
+            "-x".into(),
+            "c++-module".into(),
+            source.clean().display().to_string(),
+        ]);
+        // <--precompile>
+        res.push("--precompile".into());
+        // <-o file.pcm>
+        res.extend_from_slice(&["-o".into(), output.clean().display().to_string()]);
+        // <-MMD -MF file.d>
+        let depfile = FilesystemManager::obj_to_dep(output);
+        res.extend_from_slice(&[
+            "-MMD".into(),
+            "-MF".into(),
+            depfile.clean().display().to_string(),
+        ]);
+
+        Some(res)
+    }
+
+    /// flags are ordered this way: <std> <cflags> <lang_cflags> <target cflags> <-I...> <pkg-config cflags> <-x c++-header file.hpp> <-o file.pch> <-MMD -MF file.d>
+    #[inline]
+    pub fn full_pch_args(ctx: &Context, step: &Step) -> Option<Vec<String>> {
+        let Step::PrecompiledHeader {
+            header,
+            output,
+            preset,
+            target,
         } = step
         else {
             return None;
         };
         let mut res = Vec::default();
-        let preset = self.ctx.config.presets.get(preset).unwrap();
+        let preset = ctx.config.presets.get(preset).unwrap();
+        let target = ctx.targets.get(target)?;
 
         // <std>
-        res.push(format!("-std=c++{}", self.ctx.config.std_as_str().unwrap()));
+        res.push(format!("-std=c++{}", ctx.config.std_as_str().unwrap()));
+        // <cflags>
+        res.extend_from_slice(&preset.cflags);
+        // <lang_cflags>
+        if let Some(flags) = preset.lang_cflags.get(Language::Cpp.name()) {
+            res.extend_from_slice(flags);
+        }
+        // <target cflags>
+        res.extend_from_slice(&target.cflags);
+        // <-I...>
+        ctx.config
+            .include_dirs
+            .iter()
+            .filter_map(|p| p.to_str())
+            .for_each(|s| res.push(format!("-I{s}")));
+        // <pkg-config cflags>
+        res.extend_from_slice(&ctx.packages.cflags);
+        // <-x c++-header file.hpp>
+        res.extend_from_slice(&[
+            "-x".into(),
+            "c++-header".into(),
+            header.clean().display().to_string(),
+        ]);
+        // <-o file.pch>
+        res.extend_from_slice(&["-o".into(), output.clean().display().to_string()]);
+        // <-MMD -MF file.d>
+        let depfile = FilesystemManager::obj_to_dep(output);
+        res.extend_from_slice(&[
+            "-MMD".into(),
+            "-MF".into(),
+            depfile.clean().display().to_string(),
+        ]);
 
+        Some(res)
+    }
+
+    /// flags are ordered this way: <std> <-shared -Wl,-soname,libfoo.so.1> <file.o...> <libfile.a...> <lflags> <target lflags> <-Wl,-rpath,$ORIGIN> <-L...> <-l...> <pkg-config libs> <-o file.exe>
+    /// Libraries of the same project are passed by path, shared library is linked with '-shared'.
+    #[inline]
+    pub fn full_largs(ctx: &Context, step: &Step) -> Option<Vec<String>> {
+        let Step::Linkage {
+            source,
+            output,
+            preset,
+            target,
+        } = step
+        else {
+            return None;
+        };
+        let mut res = Vec::default();
+        let preset = ctx.config.presets.get(preset).unwrap();
+        let target = ctx.targets.get(target)?;
+
+        // <std>
+        res.push(format!("-std=c++{}", ctx.config.std_as_str().unwrap()));
+        // <-shared -Wl,-soname,libfoo.so.1>
+        if target.kind == TargetKind::SharedLib {
+            res.push("-shared".into());
+            res.extend(Self::soname_flag(target));
+        }
         // <file.o...>
         let source: Vec<String> = source
             .iter()
             .map(|p| p.clean().display().to_string())
             .collect();
         res.extend_from_slice(&source);
+        // <libfile.a...>
+        res.extend(target.libs.iter().map(|p| p.clean().display().to_string()));
         // <lflags>
         res.extend_from_slice(&preset.lflags);
+        // <target lflags>
+        res.extend_from_slice(&target.lflags);
+        // <-Wl,-rpath,$ORIGIN>
+        if target.rpath {
+            res.extend(Self::rpath_flags(target));
+        }
         // <-L...>
-        self.ctx
-            .config
+        ctx.config
             .lib_dirs
             .iter()
             .filter_map(|p| p.to_str())
             .for_each(|s| res.push(format!("-L{s}")));
         // <-l...>
         preset.libs.iter().for_each(|s| res.push(format!("-l{s}")));
+        // <pkg-config libs>
+        res.extend_from_slice(&ctx.packages.libs);
         // <-o file.exe>
         res.extend_from_slice(&["-o".into(), output.clean().display().to_string()]);
 
         Some(res)
     }
+
+    /// Name shared library is found by at runtime, macOS records it as install name.
+    fn soname_flag(target: &BuildTarget) -> Option<String> {
+        let soname = target.soname()?;
+        if cfg!(target_os = "windows") {
+            None
+        } else if cfg!(target_os = "macos") {
+            Some(format!("-Wl,-install_name,@rpath/{soname}"))
+        } else {
+            Some(format!("-Wl,-soname,{soname}"))
+        }
+    }
+
+    /// Libraries of project are put next to executables, so they are searched relative to binary,
+    /// installed executable finds them in <prefix>/lib.
+    fn rpath_flags(target: &BuildTarget) -> Vec<String> {
+        let origin = if cfg!(target_os = "windows") {
+            return Vec::default();
+        } else if cfg!(target_os = "macos") {
+            "@loader_path"
+        } else {
+            "$ORIGIN"
+        };
+        let mut res = vec![format!("-Wl,-rpath,{origin}")];
+        if target.kind == TargetKind::Executable {
+            res.push(format!("-Wl,-rpath,{origin}/../lib"));
+        }
+        res
+    }
+
+    /// Variable dynamic loader searches libraries in and directories for it, None if there are no directories.
+    pub fn library_path_env(lib_dirs: &[PathBuf]) -> Option<(String, String)> {
+        let var = if cfg!(target_os = "windows") {
+            "PATH"
+        } else if cfg!(target_os = "macos") {
+            "DYLD_LIBRARY_PATH"
+        } else {
+            "LD_LIBRARY_PATH"
+        };
+        let dirs = env::join_paths(lib_dirs.iter().map(|d| d.clean())).ok()?;
+        (!lib_dirs.is_empty()).then(|| (var.into(), dirs.to_string_lossy().to_string()))
+    }
+
+    /// flags are ordered this way: <rcsD> <libfile.a> <file.o...>
+    /// Deterministic mode zeroes timestamps, uids and modes of members, so equal objects give equal archive.
+    #[inline]
+    pub fn full_archive_args(step: &Step) -> Option<Vec<String>> {
+        let Step::Archive { source, output, .. } = step else {
+            return None;
+        };
+        let mut res = Vec::default();
+
+        // <rcsD>
+        res.push("rcsD".into());
+        // <libfile.a>
+        res.push(output.clean().display().to_string());
+        // <file.o...>
+        res.extend(source.iter().map(|p| p.clean().display().to_string()));
+
+        Some(res)
+    }
+
+    /// flags are ordered this way: <deterministic flags> <-C parent> <-cf archive.tar.gz> <dir>
+    /// Entries are sorted, owned by root and dated by epoch, gzip omits name and time,
+    /// so same files always give same archive.
+    pub fn full_tarball_args(step: &Step) -> Option<Vec<String>> {
+        let Step::Tarball { source, output } = step else {
+            return None;
+        };
+        let source = source.clean();
+        let mut res: Vec<String> = [
+            "--sort=name",
+            "--mtime=@0",
+            "--owner=0",
+            "--group=0",
+            "--numeric-owner",
+            "--mode=u=rwX,go=rX",
+            "--format=gnu",
+            "--use-compress-program=gzip -n",
+        ]
+        .map(String::from)
+        .into();
+
+        // <-C parent>
+        res.push("-C".into());
+        let parent = source.parent().filter(|p| !p.as_os_str().is_empty());
+        res.push(parent.unwrap_or(Path::new(".")).display().to_string());
+        // <-cf archive.tar.gz>
+        res.push("-cf".into());
+        res.push(output.clean().display().to_string());
+        // <dir>
+        res.push(source.file_name()?.to_string_lossy().to_string());
+
+        Some(res)
+    }
 }
 
 impl PlanVisitor for PlanExecutor<'_> {
     fn visit_compilation(&self, step: &Step) {
-        let Some(args) = self.full_cargs(step) else {
+        let Some(args) = Self::full_cargs(self.ctx, step) else {
             return;
         };
 
-        let Step::Compilation { source, .. } = step else {
+        let Step::Compilation {
+            source,
+            output,
+            target,
+            ..
+        } = step
+        else {
             return;
         };
+        let imports = Self::imported_outputs(self.ctx, source, target);
+        if !self.wait_for_imports(&imports) {
+            return;
+        }
         Logger::info(&format!(
             "Compilation started: {}",
             source.clean().display()
         ));
+        let job = CompilationJob {
+            exe: Language::of(source).compiler().into(),
+            args,
+            output: output.clone(),
+            compiler_version: self.ctx.compiler_version.clone(),
+            cache: self.object_cache.clone(),
+        };
+        self.dispatch(job, source, imports);
+    }
 
-        let tx = self.compilation_tx.borrow();
-        if tx.is_none() {
+    /// Module is precompiled on thread pool, as soon as modules it imports are ready.
+    fn visit_module_interface(&self, step: &Step) {
+        let Some(args) = Self::full_bmi_args(self.ctx, step) else {
+            return;
+        };
+        let Step::ModuleInterface {
+            source,
+            output,
+            target,
+            ..
+        } = step
+        else {
+            return;
+        };
+
+        let imports = Self::imported_outputs(self.ctx, source, target);
+        if !self.wait_for_imports(&imports) {
             return;
         }
-        self.compilation_timer.start();
-        ProcSpawner::spawn_into_pool(
-            COMPILER.into(),
+        Logger::info(&format!(
+            "Precompiling module: {}",
+            source.clean().display()
+        ));
+        // BMI embeds pathes of its inputs, so it is never taken from object cache.
+        let job = CompilationJob {
+            exe: COMPILER.into(),
             args,
-            &self.ctx.thread_pool,
-            tx.as_ref().unwrap().clone(),
-        );
+            output: output.clone(),
+            compiler_version: self.ctx.compiler_version.clone(),
+            cache: None,
+        };
+        self.dispatch(job, source, imports);
+        self.pending_modules.borrow_mut().insert(output.clone());
+    }
+
+    /// Header is precompiled before any translation unit which includes it.
+    fn visit_precompiled_header(&self, step: &Step) {
+        let Some(args) = Self::full_pch_args(self.ctx, step) else {
+            return;
+        };
+        let Step::PrecompiledHeader { header, output, .. } = step else {
+            return;
+        };
+
+        Logger::info(&format!(
+            "Precompiling header: {}",
+            header.clean().display()
+        ));
+        let tag = CompilationTag {
+            source: header.clone(),
+            output: output.clone(),
+            fingerprint: BuildDatabase::fingerprint(&self.ctx.compiler_version, &args),
+            imports: vec![],
+        };
+        if self.accept_blocking(ProcSpawner::spawn_and_wait(COMPILER, &args)) {
+            self.record_compilation(tag, &mut HashMap::default());
+        }
     }
 
     fn visit_linkage(&self, step: &Step) {
         if !self.accept_compilation() {
             return;
         }
-        let Some(args) = self.full_largs(step) else {
+        let Some(args) = Self::full_largs(self.ctx, step) else {
             return;
         };
 
-        Logger::info("Linking executable");
-        self.accept_linkage(ProcSpawner::spawn_and_wait(COMPILER, &args));
+        let Step::Linkage { output, target, .. } = step else {
+            return;
+        };
+
+        Logger::info(&format!("Linking {target}"));
+        let fingerprint = BuildDatabase::fingerprint(&self.ctx.compiler_version, &args);
+        if self.accept_blocking(ProcSpawner::spawn_and_wait(COMPILER, &args)) {
+            self.build_db.borrow_mut().insert_link(output, fingerprint);
+        }
+    }
+
+    /// Archive is recreated from scratch, so objects of removed sources don't stay in it.
+    fn visit_archive(&self, step: &Step) {
+        if !self.accept_compilation() {
+            return;
+        }
+        let Some(args) = Self::full_archive_args(step) else {
+            return;
+        };
+        let Step::Archive { output, target, .. } = step else {
+            return;
+        };
+
+        Logger::info(&format!("Archiving {target}"));
+        if let Err(err) = self.fs_m.delete(output) {
+            self.diagnostics.borrow_mut().report_error(err);
+            return;
+        }
+        let fingerprint = BuildDatabase::fingerprint(&self.ctx.archiver, &args);
+        if self.accept_blocking(ProcSpawner::spawn_and_wait(&self.ctx.archiver, &args)) {
+            self.build_db.borrow_mut().insert_link(output, fingerprint);
+        }
     }
 
     /// Will print all diagnostics before launch.
     fn visit_run(&self, step: &Step) {
-        let Step::Run { exe, args } = step else {
+        let Step::Run {
+            exe,
+            args,
+            lib_dirs,
+        } = step
+        else {
             return;
         };
 
@@ -232,7 +748,19 @@ impl PlanVisitor for PlanExecutor<'_> {
         self.diagnostics.borrow_mut().print_all_clear();
         Logger::info(&format!("Running: {exe}"));
 
-        match ProcSpawner::spawn_into_parent(exe, args) {
+        // Directories go before ones already set in environment.
+        let envs: Vec<(String, String)> = Self::library_path_env(lib_dirs)
+            .map(|(var, dirs)| {
+                let inherited = env::var_os(&var).unwrap_or_default();
+                let all = env::split_paths(&dirs)
+                    .chain(env::split_paths(&inherited))
+                    .filter(|p| !p.as_os_str().is_empty());
+                let joined = env::join_paths(all).map(|p| p.to_string_lossy().to_string());
+                (var, joined.unwrap_or(dirs))
+            })
+            .into_iter()
+            .collect();
+        match ProcSpawner::spawn_into_parent(exe, args, &envs) {
             Ok(code) if code != 0 => {
                 Logger::error(&format!("Program did not finish successfully: [{code}]"))
             }
@@ -275,6 +803,77 @@ impl PlanVisitor for PlanExecutor<'_> {
         if let Err(err) = self.fs_m.delete(path) {
             self.diagnostics.borrow_mut().report_error(err);
         }
+        self.build_db.borrow_mut().remove(path);
+    }
+
+    fn visit_make_symlink(&self, step: &Step) {
+        let Step::CreateSymlink { path, original } = step else {
+            return;
+        };
+        if let Err(err) = self.fs_m.symlink(path, original) {
+            self.diagnostics.borrow_mut().report_error(err);
+        }
+    }
+
+    fn visit_copy_file(&self, step: &Step) {
+        let Step::CopyFile { source, path } = step else {
+            return;
+        };
+        // Sources may be outputs of linkage or archiving planned before.
+        if !self.accept_compilation() {
+            return;
+        }
+        if let Err(err) = self.fs_m.copy(source, path) {
+            self.diagnostics.borrow_mut().report_error(err);
+        }
+    }
+
+    fn visit_tarball(&self, step: &Step) {
+        // Staged files are copied by steps planned before.
+        if !self.accept_compilation() {
+            return;
+        }
+        let Some(args) = Self::full_tarball_args(step) else {
+            return;
+        };
+        let Step::Tarball { output, .. } = step else {
+            return;
+        };
+
+        Logger::info(&format!("Packing {}", output.clean().display()));
+        if let Err(err) = self.fs_m.delete(output) {
+            self.diagnostics.borrow_mut().report_error(err);
+            return;
+        }
+        self.accept_blocking(ProcSpawner::spawn_and_wait(TAR, &args));
+    }
+
+    fn visit_checksum(&self, step: &Step) {
+        let Step::Checksum { source, output } = step else {
+            return;
+        };
+        if !self.accept_compilation() {
+            return;
+        }
+        let Some(hash) = FilesystemManager::hash_file(source) else {
+            self.diagnostics
+                .borrow_mut()
+                .report_error_str(format!("Can't read '{}'", source.display()));
+            return;
+        };
+        let name = source.file_name().unwrap_or_default().to_string_lossy();
+        if let Err(err) = self.fs_m.write(output, &format!("{hash}  {name}\n")) {
+            self.diagnostics.borrow_mut().report_error(err);
+        }
+    }
+
+    fn visit_write_file(&self, step: &Step) {
+        let Step::WriteFile { path, content } = step else {
+            return;
+        };
+        if let Err(err) = self.fs_m.write(path, content) {
+            self.diagnostics.borrow_mut().report_error(err);
+        }
     }
 }
 
@@ -0,0 +1,155 @@
+use path_clean::PathClean;
+
+use crate::{
+    core::Context,
+    drivers::language::Language,
+    logger::Logger,
+    meta::{COMPILER, TAR},
+    planning::{plan::PlanVisitor, step::Step},
+};
+
+use super::PlanExecutor;
+
+/// Prints every step instead of executing it (--dry-run).
+pub struct PlanPrinter<'a> {
+    ctx: &'a Context,
+}
+
+impl<'a> PlanPrinter<'a> {
+    pub fn new(ctx: &'a Context) -> Self {
+        Self { ctx }
+    }
+
+    pub fn print(&self) {
+        if self.ctx.plan.steps().is_empty() {
+            Logger::info("Nothing to do");
+        }
+        for step in self.ctx.plan.steps() {
+            step.accept(self);
+        }
+    }
+}
+
+impl PlanVisitor for PlanPrinter<'_> {
+    fn visit_compilation(&self, step: &Step) {
+        let (Some(args), Step::Compilation { source, .. }) =
+            (PlanExecutor::full_cargs(self.ctx, step), step)
+        else {
+            return;
+        };
+        let compiler = Language::of(source).compiler();
+        Logger::info(&format!("Compile: {compiler} {}", args.join(" ")));
+    }
+
+    fn visit_module_interface(&self, step: &Step) {
+        if let Some(args) = PlanExecutor::full_bmi_args(self.ctx, step) {
+            Logger::info(&format!("Precompile module: {COMPILER} {}", args.join(" ")));
+        }
+    }
+
+    fn visit_precompiled_header(&self, step: &Step) {
+        if let Some(args) = PlanExecutor::full_pch_args(self.ctx, step) {
+            Logger::info(&format!("Precompile: {COMPILER} {}", args.join(" ")));
+        }
+    }
+
+    fn visit_linkage(&self, step: &Step) {
+        if let Some(args) = PlanExecutor::full_largs(self.ctx, step) {
+            Logger::info(&format!("Link: {COMPILER} {}", args.join(" ")));
+        }
+    }
+
+    fn visit_archive(&self, step: &Step) {
+        if let Some(args) = PlanExecutor::full_archive_args(step) {
+            Logger::info(&format!(
+                "Archive: {} {}",
+                self.ctx.archiver,
+                args.join(" ")
+            ));
+        }
+    }
+
+    fn visit_run(&self, step: &Step) {
+        if let Step::Run {
+            exe,
+            args,
+            lib_dirs,
+        } = step
+        {
+            let env = PlanExecutor::library_path_env(lib_dirs)
+                .map(|(var, dirs)| format!("{var}={dirs} "))
+                .unwrap_or_default();
+            Logger::info(&format!("Run: {env}{exe} {}", args.join(" ")));
+        }
+    }
+
+    fn visit_make_dir(&self, step: &Step) {
+        if let Step::CreateDir { path } = step {
+            Logger::info(&format!("Create directory: {}", path.clean().display()));
+        }
+    }
+
+    fn visit_remove_dir(&self, step: &Step) {
+        if let Step::RemoveDir { path } = step {
+            Logger::info(&format!("Remove directory: {}", path.clean().display()));
+        }
+    }
+
+    fn visit_make_file(&self, step: &Step) {
+        if let Step::CreateFile { path } = step {
+            Logger::info(&format!("Create file: {}", path.clean().display()));
+        }
+    }
+
+    fn visit_remove_file(&self, step: &Step) {
+        if let Step::RemoveFile { path } = step {
+            Logger::info(&format!("Remove file: {}", path.clean().display()));
+        }
+    }
+
+    fn visit_make_symlink(&self, step: &Step) {
+        if let Step::CreateSymlink { path, original } = step {
+            Logger::info(&format!(
+                "Create symlink: {} -> {}",
+                path.clean().display(),
+                original.display()
+            ));
+        }
+    }
+
+    fn visit_copy_file(&self, step: &Step) {
+        if let Step::CopyFile { source, path } = step {
+            Logger::info(&format!(
+                "Copy: {} -> {}",
+                source.clean().display(),
+                path.clean().display()
+            ));
+        }
+    }
+
+    fn visit_tarball(&self, step: &Step) {
+        if let Some(args) = PlanExecutor::full_tarball_args(step) {
+            Logger::info(&format!("Pack: {TAR} {}", args.join(" ")));
+        }
+    }
+
+    fn visit_checksum(&self, step: &Step) {
+        if let Step::Checksum { source, output } = step {
+            Logger::info(&format!(
+                "Checksum: {} -> {}",
+                source.clean().display(),
+                output.clean().display()
+            ));
+        }
+    }
+
+    fn visit_write_file(&self, step: &Step) {
+        if let Step::WriteFile { path, content } = step {
+            Logger::info(&format!(
+                "Write file: {} ({} bytes)",
+                path.clean().display(),
+                content.len()
+            ));
+        }
+    }
+}
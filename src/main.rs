@@ -17,7 +17,7 @@ pub mod test_utils;
 
 fn main() {
     if cfg!(debug_assertions) {
-        test_utils::set_dir_to_tests();
+        let _dir = test_utils::set_dir_to_tests();
     }
 
     let mut core = Core::default();
@@ -30,6 +30,16 @@ fn main() {
     core.parse_config();
     core.verify_diagnostics();
 
+    if core.is_workspace() {
+        core.run_workspace();
+        core.verify_diagnostics();
+        core.print_all_diagnostics();
+        return;
+    }
+
+    core.build_dependencies();
+    core.verify_diagnostics();
+
     core.make_plan();
     core.verify_diagnostics();
 
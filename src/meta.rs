@@ -8,12 +8,25 @@ Commands:
   run               Same as build, but also run executable at the end
   test              Build and run test_runner with 'test' preset
   init              Initialize new project in current directory
+  clean             Remove build artifacts of all presets
+  install           Build with 'release' preset and install into prefix
+  uninstall         Remove everything installed by 'install'
+  package           Build with 'release' preset and pack into versioned .tar.gz
+  compdb            Write compile_commands.json without building
+  cache stats       Show size of local object cache
+  cache clear       Remove all entries of local object cache
 
 Options:
   -h, --help        Show this help message and exit
   -v, --version     Show version
   -f, --force       Forced build, ignored with 'test' command
-  --preset=...      Specify preset for build
+  --preset=...      Specify preset for build, or preset to clean
+  --target=...      Build (or run) only this target and its dependencies
+  --lib             Archive all sources except entry points into static library
+  --member=...      Workspace root: build only this member and members it uses
+  --prefix=...      Install into this directory instead of 'install.prefix'
+  --objects-only    Clean only object files
+  --dry-run         Print planned steps without executing them
 
 Variadic:
     [files]         Specify file with 'main()' function, it will be included in build proccess, and other entry points will be ignored
@@ -21,9 +34,16 @@ Variadic:
 Examples:
   prog build --force
   prog run src/main.cpp
-  prog test -- --gtest_filter=MyTestSuite.*";
+  prog test -- --gtest_filter=MyTestSuite.*
+  prog clean --preset=release --dry-run";
 
 pub const VERSION_MSG: &str =
     "C.U.M. 0.1.0\nCopyright (c) 2025 Zloy Kot\nCompilation unit manager for clang++.";
 
 pub const COMPILER: &str = "clang++";
+pub const C_COMPILER: &str = "clang";
+pub const ARCHIVER: &str = "llvm-ar";
+/// Used if llvm-ar is not installed.
+pub const FALLBACK_ARCHIVER: &str = "ar";
+/// GNU tar, for packages.
+pub const TAR: &str = "tar";
@@ -1,12 +1,8 @@
-use std::{
-    cell::RefCell,
-    collections::{HashMap, HashSet},
-    rc::Rc,
-};
+use std::collections::{HashMap, HashSet};
 
 use crate::errors::QueryError;
 
-#[derive(Default, Debug, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct Args {
     /// First argument from command line (init, build, etc.).
     pub command: Option<String>,
@@ -104,6 +100,7 @@ impl Args {
     }
 }
 
+#[cfg(test)]
 pub mod tests {
 
     use crate::{core::Context, parsing::arg_parser::Args};
@@ -112,7 +109,7 @@ pub mod tests {
 
     #[test]
     fn simple_args_regression() {
-        let mock_args: Vec<String> = vec![
+        let mock_args: Vec<String> = [
             "cum.exe",
             "build",
             "--config=release",
@@ -127,8 +124,10 @@ pub mod tests {
         let mut mock_ctx = Context::default();
         let mut parser = ArgParser::new(mock_args, &mut mock_ctx.args);
 
-        let mut expected_args = Args::default();
-        expected_args.command = Some("build".to_string());
+        let mut expected_args = Args {
+            command: Some("build".to_string()),
+            ..Default::default()
+        };
         expected_args.flags.insert("v".to_string());
         expected_args
             .named_params
@@ -146,7 +145,7 @@ pub mod tests {
 
     #[test]
     fn simple_args_param_as_flag() {
-        let mock_args: Vec<String> = vec!["cum.exe", "--help"]
+        let mock_args: Vec<String> = ["cum.exe", "--help"]
             .iter()
             .map(|s| String::from(*s))
             .collect();
@@ -1,8 +1,19 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    iter,
+    path::{Path, PathBuf},
+};
 
+use ignore::overrides::OverrideBuilder;
 use path_clean::PathClean;
 use serde::Deserialize;
 
+use crate::{
+    drivers::{language::LANGUAGE_NAMES, vendor::VENDOR_DIR},
+    errors::ParsingError,
+    planning::build_target::IMPLICIT_DIR,
+};
+
 pub const CONFIG_FILE_PATH: &str = "./Cum.toml";
 pub const DEFAULT_CONFIG_STR: &str = include_str!("../../assets/default_config.toml");
 
@@ -11,20 +22,217 @@ pub struct Config {
     #[serde(default)]
     pub std: u8,
 
+    /// Standard of C sources.
+    #[serde(default)]
+    pub c_std: u8,
+
     #[serde(default)]
     pub include_dirs: Vec<PathBuf>,
 
     #[serde(default)]
     pub lib_dirs: Vec<PathBuf>,
 
+    /// System packages queried with pkg-config: "zlib" or "sdl2 >= 2.26".
+    #[serde(default)]
+    pub pkg_config: Vec<String>,
+
     #[serde(default)]
     pub target_name: String,
 
     #[serde(default)]
     pub entry_points: Vec<PathBuf>,
 
+    /// Directories searched for sources.
+    #[serde(default)]
+    pub sources: Vec<PathBuf>,
+
+    /// Extensions of sources, their language is decided by extension.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+
+    /// Glob patterns relative to project root, matching files are never compiled.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Skip sources ignored by .gitignore files.
+    #[serde(default)]
+    pub gitignore: Option<bool>,
+
+    /// Header precompiled once per preset and included into every translation unit.
+    #[serde(default)]
+    pub pch: Option<PathBuf>,
+
     #[serde(default)]
     pub presets: HashMap<String, Preset>,
+
+    /// Executables and libraries of project, if empty 'target_name' executable is built.
+    #[serde(default)]
+    pub targets: Vec<TargetConfig>,
+
+    /// What dependents of project get, when it is used by other workspace member.
+    #[serde(default)]
+    pub export: ExportConfig,
+
+    /// Other cum projects this one uses, by name. Ordered, as their libraries are linked in this order.
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, DependencyConfig>,
+
+    /// Projects built together by root manifest, root itself is not built.
+    #[serde(default)]
+    pub workspace: Option<WorkspaceConfig>,
+
+    #[serde(default)]
+    pub cache: CacheConfig,
+
+    #[serde(default)]
+    pub install: InstallConfig,
+
+    #[serde(default)]
+    pub package: PackageConfig,
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct ExportConfig {
+    /// Relative to project, added to include directories of dependents.
+    #[serde(default)]
+    pub include_dirs: Vec<PathBuf>,
+
+    /// Compile definitions dependents need: 'NAME' or 'NAME=value'.
+    #[serde(default)]
+    pub defines: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct DependencyConfig {
+    /// Directory of local project, relative to this one.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+
+    /// Git repository (file:// as well) checked out at 'tag' or 'rev'.
+    #[serde(default)]
+    pub git: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub rev: Option<String>,
+
+    /// Tarball (url or local file) verified with 'sha256'.
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub sha256: Option<String>,
+
+    /// Sources of git or tarball dependency, relative to its root: ["src"] if empty.
+    #[serde(default)]
+    pub sources: Vec<PathBuf>,
+    /// Used by dependency and its dependents: ["include"] if empty.
+    #[serde(default)]
+    pub include_dirs: Vec<PathBuf>,
+    /// Added to preset cflags when sources of dependency are compiled.
+    #[serde(default)]
+    pub cflags: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct WorkspaceConfig {
+    /// Directories of member projects, member is named after its directory.
+    #[serde(default)]
+    pub members: Vec<PathBuf>,
+
+    /// Member name -> members it uses.
+    #[serde(default)]
+    pub dependencies: HashMap<String, Vec<String>>,
+}
+
+/// Layout of 'cum install': <prefix>/bin, <prefix>/lib and <prefix>/include
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct InstallConfig {
+    /// Overridden with --prefix, DESTDIR is prepended to it.
+    #[serde(default)]
+    pub prefix: Option<PathBuf>,
+
+    /// Public header files or directories, they keep their path relative to include directory.
+    #[serde(default)]
+    pub headers: Vec<PathBuf>,
+}
+
+/// Archive of 'cum package': target/package/<name>-<version>.tar.gz
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct PackageConfig {
+    /// 'target_name' if not set.
+    #[serde(default)]
+    pub name: Option<String>,
+
+    #[serde(default)]
+    pub version: Option<String>,
+
+    /// Extra files or directories (README, LICENSE, assets), staged with their relative path.
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+}
+
+/// Local compilation cache shared between projects.
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct CacheConfig {
+    #[serde(default)]
+    pub enabled: Option<bool>,
+
+    #[serde(default)]
+    pub max_size_mb: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TargetKind {
+    #[default]
+    Executable,
+    StaticLib,
+    SharedLib,
+}
+
+/// Single '[[targets]]' entry.
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct TargetConfig {
+    pub name: String,
+
+    #[serde(default)]
+    pub kind: TargetKind,
+
+    /// Directories or files searched for sources, entry points of other targets are skipped.
+    #[serde(default)]
+    pub sources: Vec<PathBuf>,
+
+    /// File with 'main()' function of executable.
+    #[serde(default)]
+    pub entry_point: Option<PathBuf>,
+
+    /// Added to cflags of preset.
+    #[serde(default)]
+    pub cflags: Vec<String>,
+
+    /// Added to lflags of preset.
+    #[serde(default)]
+    pub lflags: Vec<String>,
+
+    /// Libraries of the same project linked into target.
+    #[serde(default)]
+    pub deps: Vec<String>,
+
+    /// Version of shared library: 'libfoo.so.1.2.0' is linked with soname 'libfoo.so.1'.
+    #[serde(default)]
+    pub version: Option<String>,
+
+    /// Symbols of target are exported only if marked explicitly, when hidden.
+    #[serde(default)]
+    pub visibility: Visibility,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    Default,
+    Hidden,
 }
 
 #[derive(Debug, Default, Deserialize, Clone)]
@@ -40,6 +248,27 @@ pub struct Preset {
 
     #[serde(default)]
     pub target_folder: PathBuf,
+
+    /// Flags added to cflags for sources of single language: cpp, c, objcpp or asm.
+    #[serde(default)]
+    pub lang_cflags: HashMap<String, Vec<String>>,
+}
+
+impl WorkspaceConfig {
+    /// Last component of member directory: libs/core is named core.
+    pub fn member_name(dir: &Path) -> String {
+        dir.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn dependencies_of(&self, name: &str) -> &[String] {
+        self.dependencies
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
 }
 
 impl Config {
@@ -47,6 +276,9 @@ impl Config {
         if other.std != 0 {
             self.std = other.std;
         }
+        if other.c_std != 0 {
+            self.c_std = other.c_std;
+        }
 
         if !other.include_dirs.is_empty() {
             self.include_dirs = other.include_dirs;
@@ -60,14 +292,70 @@ impl Config {
         if !other.entry_points.is_empty() {
             self.entry_points = other.entry_points;
         }
+        if !other.sources.is_empty() {
+            self.sources = other.sources;
+        }
+        if !other.extensions.is_empty() {
+            self.extensions = other.extensions;
+        }
+        if !other.exclude.is_empty() {
+            self.exclude = other.exclude;
+        }
+        if other.gitignore.is_some() {
+            self.gitignore = other.gitignore;
+        }
+        if other.pch.is_some() {
+            self.pch = other.pch;
+        }
+        if !other.pkg_config.is_empty() {
+            self.pkg_config = other.pkg_config;
+        }
+        if !other.targets.is_empty() {
+            self.targets = other.targets;
+        }
+        if !other.export.include_dirs.is_empty() {
+            self.export.include_dirs = other.export.include_dirs;
+        }
+        if !other.export.defines.is_empty() {
+            self.export.defines = other.export.defines;
+        }
+        if !other.dependencies.is_empty() {
+            self.dependencies = other.dependencies;
+        }
+        if other.workspace.is_some() {
+            self.workspace = other.workspace;
+        }
+
+        if other.install.prefix.is_some() {
+            self.install.prefix = other.install.prefix;
+        }
+        if !other.install.headers.is_empty() {
+            self.install.headers = other.install.headers;
+        }
+
+        if other.package.name.is_some() {
+            self.package.name = other.package.name;
+        }
+        if other.package.version.is_some() {
+            self.package.version = other.package.version;
+        }
+        if !other.package.files.is_empty() {
+            self.package.files = other.package.files;
+        }
+
+        if other.cache.enabled.is_some() {
+            self.cache.enabled = other.cache.enabled;
+        }
+        if other.cache.max_size_mb.is_some() {
+            self.cache.max_size_mb = other.cache.max_size_mb;
+        }
 
         for (key, value) in other.presets {
-            if !self.presets.contains_key(&key) {
+            let Some(preset) = self.presets.get_mut(&key) else {
                 self.presets.insert(key, value);
                 continue;
-            }
+            };
 
-            let preset = self.presets.get_mut(&key).unwrap();
             if !value.cflags.is_empty() {
                 preset.cflags = value.cflags.clone();
             }
@@ -80,6 +368,7 @@ impl Config {
             if value.target_folder != PathBuf::default() {
                 preset.target_folder = value.target_folder.clone();
             }
+            preset.lang_cflags.extend(value.lang_cflags);
         }
     }
 
@@ -87,6 +376,184 @@ impl Config {
         self.include_dirs.iter_mut().for_each(|p| *p = p.clean());
         self.lib_dirs.iter_mut().for_each(|p| *p = p.clean());
         self.entry_points.iter_mut().for_each(|p| *p = p.clean());
+        self.sources.iter_mut().for_each(|p| *p = p.clean());
+        self.pch = self.pch.as_ref().map(|p| p.clean());
+        for target in &mut self.targets {
+            target.sources.iter_mut().for_each(|p| *p = p.clean());
+            target.entry_point = target.entry_point.as_ref().map(|p| p.clean());
+        }
+        self.install.headers.iter_mut().for_each(|p| *p = p.clean());
+        self.package.files.iter_mut().for_each(|p| *p = p.clean());
+        self.export
+            .include_dirs
+            .iter_mut()
+            .for_each(|p| *p = p.clean());
+        for dependency in self.dependencies.values_mut() {
+            dependency.path = dependency.path.as_ref().map(|p| p.clean());
+            dependency.sources.iter_mut().for_each(|p| *p = p.clean());
+            dependency
+                .include_dirs
+                .iter_mut()
+                .for_each(|p| *p = p.clean());
+        }
+        if let Some(workspace) = &mut self.workspace {
+            workspace.members.iter_mut().for_each(|p| *p = p.clean());
+        }
+    }
+
+    /// Targets ordered so that every target goes after its dependencies.
+    /// Names have to be unique and not taken by object directories of implicit target
+    /// or fetched dependencies, dependencies have to be known libraries without cycles.
+    pub fn targets_in_order(&self) -> Result<Vec<&TargetConfig>, ParsingError> {
+        let mut names = HashSet::new();
+        for target in &self.targets {
+            if [IMPLICIT_DIR, VENDOR_DIR].contains(&target.name.as_str()) {
+                return Err(ParsingError::ReservedTarget(target.name.clone()));
+            }
+            if !names.insert(target.name.as_str()) {
+                return Err(ParsingError::DuplicateTarget(target.name.clone()));
+            }
+        }
+        for target in &self.targets {
+            for dep in &target.deps {
+                match self.targets.iter().find(|t| &t.name == dep) {
+                    None => {
+                        return Err(ParsingError::UnknownTarget(
+                            dep.clone(),
+                            target.name.clone(),
+                        ))
+                    }
+                    Some(t) if t.kind == TargetKind::Executable => {
+                        return Err(ParsingError::ExecutableDependency(
+                            dep.clone(),
+                            target.name.clone(),
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut res: Vec<&TargetConfig> = Vec::default();
+        while res.len() < self.targets.len() {
+            let ready: Vec<&TargetConfig> = self
+                .targets
+                .iter()
+                .filter(|t| !res.iter().any(|r| r.name == t.name))
+                .filter(|t| t.deps.iter().all(|d| res.iter().any(|r| &r.name == d)))
+                .collect();
+
+            if ready.is_empty() {
+                let cycle: Vec<&str> = self
+                    .targets
+                    .iter()
+                    .filter(|t| !res.iter().any(|r| r.name == t.name))
+                    .map(|t| t.name.as_str())
+                    .collect();
+                return Err(ParsingError::TargetCycle(cycle.join(", ")));
+            }
+            res.extend(ready);
+        }
+        Ok(res)
+    }
+
+    /// Every dependency has exactly one source: 'path', 'git' with either 'tag' or 'rev',
+    /// or 'url' with 'sha256'. Fetched dependencies are targets, so their names are unique.
+    pub fn check_dependencies(&self) -> Result<(), ParsingError> {
+        for (name, dependency) in &self.dependencies {
+            let valid = match (&dependency.path, &dependency.git, &dependency.url) {
+                (Some(_), None, None) => true,
+                (None, Some(_), None) => dependency.tag.is_some() != dependency.rev.is_some(),
+                (None, None, Some(_)) => dependency.sha256.is_some(),
+                _ => false,
+            };
+            if !valid {
+                return Err(ParsingError::InvalidDependency(name.clone()));
+            }
+            if dependency.path.is_none()
+                && (*name == self.target_name || self.targets.iter().any(|t| t.name == *name))
+            {
+                return Err(ParsingError::DuplicateTarget(name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Members ordered so that every member goes after members it uses.
+    /// Member directories have to exist, names have to be unique, without cycles.
+    pub fn members_in_order(&self) -> Result<Vec<(String, PathBuf)>, ParsingError> {
+        let Some(workspace) = &self.workspace else {
+            return Ok(vec![]);
+        };
+        let mut members: Vec<(String, PathBuf)> = Vec::default();
+        for dir in &workspace.members {
+            if !dir.is_dir() {
+                return Err(ParsingError::MissingMember(dir.clone()));
+            }
+            let name = WorkspaceConfig::member_name(dir);
+            if members.iter().any(|(n, _)| *n == name) {
+                return Err(ParsingError::DuplicateMember(name));
+            }
+            members.push((name, dir.clone()));
+        }
+        for (name, deps) in &workspace.dependencies {
+            let unknown = iter::once(name)
+                .chain(deps)
+                .find(|n| !members.iter().any(|(m, _)| m == *n));
+            if let Some(unknown) = unknown {
+                return Err(ParsingError::UnknownMember(unknown.clone()));
+            }
+        }
+
+        let mut res: Vec<(String, PathBuf)> = Vec::default();
+        while !members.is_empty() {
+            let (ready, rest): (Vec<_>, Vec<_>) = members.into_iter().partition(|(name, _)| {
+                workspace
+                    .dependencies_of(name)
+                    .iter()
+                    .all(|d| res.iter().any(|(r, _)| r == d))
+            });
+            if ready.is_empty() {
+                let cycle: Vec<&str> = rest.iter().map(|(n, _)| n.as_str()).collect();
+                return Err(ParsingError::MemberCycle(cycle.join(", ")));
+            }
+            res.extend(ready);
+            members = rest;
+        }
+        Ok(res)
+    }
+
+    #[inline]
+    pub fn c_std_as_str(&self) -> Option<String> {
+        match self.c_std {
+            89 | 99 | 11 | 17 | 23 => Some(self.c_std.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Every pattern of 'exclude' has to be valid glob.
+    pub fn check_excludes(&self) -> Result<(), ParsingError> {
+        let mut builder = OverrideBuilder::new("");
+        for pattern in &self.exclude {
+            builder
+                .add(&format!("!{pattern}"))
+                .map_err(|err| ParsingError::InvalidGlob(err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Every key of 'lang_cflags' has to name known language.
+    pub fn check_languages(&self) -> Result<(), ParsingError> {
+        let unknown = self
+            .presets
+            .values()
+            .flat_map(|p| p.lang_cflags.keys())
+            .find(|l| !LANGUAGE_NAMES.contains(&l.as_str()));
+
+        match unknown {
+            Some(lang) => Err(ParsingError::UnknownLanguage(lang.clone())),
+            None => Ok(()),
+        }
     }
 
     #[inline]
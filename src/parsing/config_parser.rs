@@ -22,6 +22,12 @@ impl<'a> ConfigParser<'a> {
         if self.cfg.std_as_str().is_none() {
             return Err(ParsingError::WrongStandart(self.cfg.std));
         }
+        if self.cfg.c_std_as_str().is_none() {
+            return Err(ParsingError::WrongStandart(self.cfg.c_std));
+        }
+        self.cfg.check_languages()?;
+        self.cfg.check_excludes()?;
+        self.cfg.targets_in_order()?;
         self.cfg.normalize_pathes();
 
         Ok(())
@@ -41,7 +47,15 @@ impl<'a> ConfigParser<'a> {
         if self.cfg.std_as_str().is_none() {
             return Err(ParsingError::WrongStandart(self.cfg.std));
         }
+        if self.cfg.c_std_as_str().is_none() {
+            return Err(ParsingError::WrongStandart(self.cfg.c_std));
+        }
+        self.cfg.check_languages()?;
+        self.cfg.check_excludes()?;
+        self.cfg.targets_in_order()?;
+        self.cfg.check_dependencies()?;
         self.cfg.normalize_pathes();
+        self.cfg.members_in_order()?;
 
         Ok(())
     }
@@ -58,8 +72,14 @@ impl<'a> ConfigParser<'a> {
     }
 }
 
+#[cfg(test)]
 pub mod tests {
-    use crate::core::Context;
+    use crate::{
+        core::Context,
+        errors::ParsingError,
+        parsing::config::{TargetConfig, TargetKind},
+        test_utils::set_dir_to_tests,
+    };
 
     use super::ConfigParser;
     use std::path::PathBuf;
@@ -75,4 +95,117 @@ pub mod tests {
 
         println!("Parsed config: {:#?}", mock_ctx.config);
     }
+
+    #[test]
+    fn targets_parsing_and_order() {
+        let str = "[[targets]]\nname = \"app\"\nentry_point = \"src/main.cpp\"\ndeps = [\"core\"]\n\n[[targets]]\nname = \"core\"\nkind = \"static-lib\"\nsources = [\"src\"]\n";
+        let mut cfg = ConfigParser::parse_from_str(str).unwrap();
+
+        let order: Vec<&str> = cfg
+            .targets_in_order()
+            .unwrap()
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect();
+        assert_eq!(order, ["core", "app"]);
+        assert_eq!(cfg.targets[0].kind, TargetKind::Executable);
+        assert_eq!(cfg.targets[1].kind, TargetKind::StaticLib);
+
+        cfg.targets[1].deps = vec!["app".into()];
+        assert!(matches!(
+            cfg.targets_in_order(),
+            Err(ParsingError::ExecutableDependency(..))
+        ));
+
+        cfg.targets[0].kind = TargetKind::SharedLib;
+        assert!(matches!(
+            cfg.targets_in_order(),
+            Err(ParsingError::TargetCycle(..))
+        ));
+
+        cfg.targets.push(TargetConfig {
+            name: "core".into(),
+            ..Default::default()
+        });
+        assert!(matches!(
+            cfg.targets_in_order(),
+            Err(ParsingError::DuplicateTarget(..))
+        ));
+    }
+
+    #[test]
+    fn workspace_members_order() {
+        let _dir = set_dir_to_tests();
+        let str = "[workspace]\nmembers = [\"src\", \"./include\"]\n\n[workspace.dependencies]\nsrc = [\"include\"]\n";
+        let mut cfg = ConfigParser::parse_from_str(str).unwrap();
+
+        let order = cfg.members_in_order().unwrap();
+        assert_eq!(
+            order,
+            [
+                ("include".into(), PathBuf::from("./include")),
+                ("src".into(), PathBuf::from("src"))
+            ]
+        );
+
+        let workspace = cfg.workspace.as_mut().unwrap();
+        workspace
+            .dependencies
+            .insert("include".into(), vec!["src".into()]);
+        assert!(matches!(
+            cfg.members_in_order(),
+            Err(ParsingError::MemberCycle(..))
+        ));
+
+        let workspace = cfg.workspace.as_mut().unwrap();
+        workspace.dependencies.insert("app".into(), vec![]);
+        assert!(matches!(
+            cfg.members_in_order(),
+            Err(ParsingError::UnknownMember(..))
+        ));
+
+        let workspace = cfg.workspace.as_mut().unwrap();
+        workspace.dependencies.clear();
+        workspace.members.push("missing".into());
+        assert!(matches!(
+            cfg.members_in_order(),
+            Err(ParsingError::MissingMember(..))
+        ));
+    }
+
+    #[test]
+    fn dependencies_parsing() {
+        let str =
+            "[dependencies]\nzlib = { path = \"../zlib/\" }\ncommon = { path = \"../common\" }\n";
+        let mut cfg = ConfigParser::parse_from_str(str).unwrap();
+        cfg.normalize_pathes();
+
+        let deps: Vec<(&str, Option<&PathBuf>)> = cfg
+            .dependencies
+            .iter()
+            .map(|(name, dep)| (name.as_str(), dep.path.as_ref()))
+            .collect();
+        assert_eq!(
+            deps,
+            [
+                ("common", Some(&PathBuf::from("../common"))),
+                ("zlib", Some(&PathBuf::from("../zlib")))
+            ]
+        );
+        assert!(cfg.check_dependencies().is_ok());
+
+        let str = "[dependencies]\nfmt = { git = \"https://github.com/fmtlib/fmt\", tag = \"11.0.0\", rev = \"0c9fce2\" }\n";
+        let cfg = ConfigParser::parse_from_str(str).unwrap();
+        assert!(matches!(
+            cfg.check_dependencies(),
+            Err(ParsingError::InvalidDependency(..))
+        ));
+
+        let str = "target_name = \"zlib\"\n[dependencies]\nzlib = { url = \"zlib.tar.gz\", sha256 = \"00\" }\n";
+        let cfg = ConfigParser::parse_from_str(str).unwrap();
+        assert!(matches!(
+            cfg.check_dependencies(),
+            Err(ParsingError::DuplicateTarget(..))
+        ));
+    }
 }
@@ -77,6 +77,17 @@ pub struct FullRun;
 pub struct RunTest;
 
 pub struct InitProject;
+pub struct Clean;
+pub struct Install;
+pub struct Uninstall;
+pub struct Package;
+pub struct CompDb;
+pub struct DryRun;
+pub struct CacheStats;
+pub struct CacheClear;
+
+/// Commands workspace root forwards to its members.
+pub struct MemberCommand;
 
 pub struct PrintHelp;
 pub struct PrintVersion;
@@ -84,7 +95,6 @@ pub struct PrintVersion;
 impl ArgsSpec for IncrementalBuild {
     fn is_satisfied_by(&self, item: &Args) -> bool {
         item.command.as_ref().map(|s| s == "build").unwrap_or(false)
-            && !item.have_flag("preset")
             && !item.have_flag("force")
             && !item.have_flag("f")
     }
@@ -93,7 +103,6 @@ impl ArgsSpec for IncrementalBuild {
 impl ArgsSpec for IncrementalRun {
     fn is_satisfied_by(&self, item: &Args) -> bool {
         item.command.as_ref().map(|s| s == "run").unwrap_or(false)
-            && !item.have_flag("preset")
             && !item.have_flag("force")
             && !item.have_flag("f")
     }
@@ -102,14 +111,14 @@ impl ArgsSpec for IncrementalRun {
 impl ArgsSpec for FullBuild {
     fn is_satisfied_by(&self, item: &Args) -> bool {
         item.command.as_ref().map(|s| s == "build").unwrap_or(false)
-            && (item.have_flag("preset") || item.have_flag("force") || item.have_flag("f"))
+            && (item.have_flag("force") || item.have_flag("f"))
     }
 }
 
 impl ArgsSpec for FullRun {
     fn is_satisfied_by(&self, item: &Args) -> bool {
         item.command.as_ref().map(|s| s == "run").unwrap_or(false)
-            && (item.have_flag("preset") || item.have_flag("force") || item.have_flag("f"))
+            && (item.have_flag("force") || item.have_flag("f"))
     }
 }
 
@@ -125,6 +134,97 @@ impl ArgsSpec for InitProject {
     }
 }
 
+impl ArgsSpec for Clean {
+    fn is_satisfied_by(&self, item: &Args) -> bool {
+        item.command.as_ref().map(|s| s == "clean").unwrap_or(false)
+    }
+}
+
+impl ArgsSpec for Install {
+    fn is_satisfied_by(&self, item: &Args) -> bool {
+        item.command
+            .as_ref()
+            .map(|s| s == "install")
+            .unwrap_or(false)
+    }
+}
+
+impl ArgsSpec for Package {
+    fn is_satisfied_by(&self, item: &Args) -> bool {
+        item.command
+            .as_ref()
+            .map(|s| s == "package")
+            .unwrap_or(false)
+    }
+}
+
+impl ArgsSpec for CompDb {
+    fn is_satisfied_by(&self, item: &Args) -> bool {
+        item.command
+            .as_ref()
+            .map(|s| s == "compdb")
+            .unwrap_or(false)
+    }
+}
+
+impl ArgsSpec for Uninstall {
+    fn is_satisfied_by(&self, item: &Args) -> bool {
+        item.command
+            .as_ref()
+            .map(|s| s == "uninstall")
+            .unwrap_or(false)
+    }
+}
+
+impl ArgsSpec for DryRun {
+    fn is_satisfied_by(&self, item: &Args) -> bool {
+        item.command.is_some() && item.have_flag("dry-run")
+    }
+}
+
+impl ArgsSpec for CacheStats {
+    fn is_satisfied_by(&self, item: &Args) -> bool {
+        item.command.as_ref().map(|s| s == "cache").unwrap_or(false)
+            && item
+                .unnamed_params
+                .first()
+                .map(|s| s == "stats")
+                .unwrap_or(false)
+    }
+}
+
+impl ArgsSpec for CacheClear {
+    fn is_satisfied_by(&self, item: &Args) -> bool {
+        item.command.as_ref().map(|s| s == "cache").unwrap_or(false)
+            && item
+                .unnamed_params
+                .first()
+                .map(|s| s == "clear")
+                .unwrap_or(false)
+    }
+}
+
+impl ArgsSpec for MemberCommand {
+    fn is_satisfied_by(&self, item: &Args) -> bool {
+        item.command
+            .as_ref()
+            .map(|s| {
+                [
+                    "build",
+                    "run",
+                    "test",
+                    "clean",
+                    "install",
+                    "uninstall",
+                    "package",
+                    "compdb",
+                ]
+                .contains(&s.as_str())
+            })
+            .unwrap_or(false)
+    }
+}
+
 impl ArgsSpec for PrintHelp {
     fn is_satisfied_by(&self, item: &Args) -> bool {
         item.command.is_none() && (item.have_flag("h") || item.have_flag("help"))
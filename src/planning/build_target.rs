@@ -0,0 +1,276 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    drivers::{fs_manager::FilesystemManager, module_graph::ModuleGraph, vendor::*},
+    parsing::config::{Config, TargetConfig, TargetKind, Visibility},
+};
+
+/// Position independent objects are kept apart from others: <obj_dir>/pic
+pub const PIC_DIR: &str = "pic";
+/// Objects of target without '[[targets]]' (or test runner): target/obj/<preset>/default
+/// Declared targets can't be named like it, so their objects never mix.
+pub const IMPLICIT_DIR: &str = "default";
+
+/// Target resolved for preset being built: where its objects and output go,
+/// which sources it is compiled from and which libraries it is linked with.
+#[derive(Debug, Default, Clone)]
+pub struct BuildTarget {
+    pub name: String,
+    pub kind: TargetKind,
+    /// Objects of target for current preset.
+    pub obj_dir: PathBuf,
+    /// Executable or library produced by target.
+    pub output: PathBuf,
+    pub sources: Vec<PathBuf>,
+    pub cflags: Vec<String>,
+    pub lflags: Vec<String>,
+    /// Outputs of libraries target depends on (including indirectly),
+    /// dependents go before their dependencies, as linker expects.
+    pub libs: Vec<PathBuf>,
+    /// Named modules of target sources.
+    pub modules: ModuleGraph,
+    /// Compiled with -fPIC: shared library or static library linked into one.
+    pub pic: bool,
+    /// Version of shared library.
+    pub version: Option<String>,
+    /// Target links with shared library of project, it is looked for next to output.
+    pub rpath: bool,
+    /// Static library of git or tarball dependency, compiled without precompiled header.
+    pub vendored: bool,
+}
+
+impl BuildTarget {
+    /// Target of project without '[[targets]]': 'target_name' executable (or static library with --lib),
+    /// objects are kept in their own directory inside of preset object directory.
+    pub fn implicit(cfg: &Config, preset: &str, kind: TargetKind) -> Self {
+        Self {
+            name: cfg.target_name.clone(),
+            kind,
+            obj_dir: FilesystemManager::obj_dir(preset).join(IMPLICIT_DIR),
+            output: Self::output_of(cfg, preset, &cfg.target_name, kind, None),
+            ..Default::default()
+        }
+    }
+
+    /// Declared target, every one has its own object directory: target/obj/<preset>/<name>
+    pub fn declared(cfg: &Config, preset: &str, target: &TargetConfig) -> Self {
+        let mut cflags = target.cflags.clone();
+        if target.visibility == Visibility::Hidden {
+            cflags.push("-fvisibility=hidden".into());
+        }
+
+        Self {
+            name: target.name.clone(),
+            kind: target.kind,
+            obj_dir: FilesystemManager::obj_dir(preset).join(&target.name),
+            output: Self::output_of(
+                cfg,
+                preset,
+                &target.name,
+                target.kind,
+                target.version.as_deref(),
+            ),
+            cflags,
+            lflags: target.lflags.clone(),
+            version: target.version.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Fetched dependency: target/obj/<preset>/vendor/<name> and target/<preset>/vendor/lib<name>.a
+    /// Its sources are absolute, so objects mirror their whole path.
+    pub fn vendored(cfg: &Config, preset: &str, vendored: &VendoredSource) -> Self {
+        let output = Self::output_of(cfg, preset, &vendored.name, TargetKind::StaticLib, None);
+        let mut cflags = vendored.cflags.clone();
+        cflags.extend(
+            vendored
+                .include_dirs
+                .iter()
+                .map(|d| format!("-I{}", d.display())),
+        );
+
+        Self {
+            name: vendored.name.clone(),
+            kind: TargetKind::StaticLib,
+            obj_dir: FilesystemManager::obj_dir(preset)
+                .join(VENDOR_DIR)
+                .join(&vendored.name),
+            output: match (output.parent(), output.file_name()) {
+                (Some(folder), Some(file)) => folder.join(VENDOR_DIR).join(file),
+                _ => output,
+            },
+            cflags,
+            vendored: true,
+            ..Default::default()
+        }
+    }
+
+    /// Platform specific file name inside of preset target folder.
+    /// Only ELF shared libraries have version in file name.
+    pub fn output_of(
+        cfg: &Config,
+        preset: &str,
+        name: &str,
+        kind: TargetKind,
+        version: Option<&str>,
+    ) -> PathBuf {
+        let folder = cfg
+            .presets
+            .get(preset)
+            .map(|p| p.target_folder.clone())
+            .unwrap_or_default();
+        let file = match kind {
+            TargetKind::Executable if cfg!(target_os = "windows") => format!("{name}.exe"),
+            TargetKind::Executable => name.to_string(),
+            TargetKind::StaticLib if cfg!(target_os = "windows") => format!("{name}.lib"),
+            TargetKind::StaticLib => format!("lib{name}.a"),
+            TargetKind::SharedLib if cfg!(target_os = "windows") => format!("{name}.dll"),
+            TargetKind::SharedLib if cfg!(target_os = "macos") => format!("lib{name}.dylib"),
+            TargetKind::SharedLib => match version {
+                Some(version) => format!("lib{name}.so.{version}"),
+                None => format!("lib{name}.so"),
+            },
+        };
+        folder.join(file)
+    }
+
+    /// Objects are compiled with -fPIC into separate directory.
+    pub fn make_pic(&mut self) {
+        if !self.pic {
+            self.pic = true;
+            self.obj_dir.push(PIC_DIR);
+            self.cflags.push("-fPIC".into());
+        }
+    }
+
+    /// Object directory without PIC subdirectory.
+    pub fn base_obj_dir(&self) -> &Path {
+        match self.obj_dir.parent() {
+            Some(parent) if self.pic => parent,
+            _ => &self.obj_dir,
+        }
+    }
+
+    /// Name dependents of shared library look for at runtime: 'libfoo.so.1' for version 1.2.0
+    pub fn soname(&self) -> Option<String> {
+        if self.kind != TargetKind::SharedLib {
+            return None;
+        }
+        match &self.version {
+            Some(version) if self.has_versioned_name() => {
+                let major = version.split('.').next().unwrap_or(version);
+                Some(format!("lib{}.so.{major}", self.name))
+            }
+            _ => self
+                .output
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string()),
+        }
+    }
+
+    /// Pairs of link and file it points to (relative to link):
+    /// libfoo.so.1 -> libfoo.so.1.2.0 and libfoo.so -> libfoo.so.1
+    pub fn symlinks(&self) -> Vec<(PathBuf, PathBuf)> {
+        let (Some(soname), Some(folder), Some(file)) = (
+            self.soname().filter(|_| self.has_versioned_name()),
+            self.output.parent(),
+            self.output.file_name(),
+        ) else {
+            return vec![];
+        };
+        let mut res = Vec::default();
+
+        if file != soname.as_str() {
+            res.push((folder.join(&soname), PathBuf::from(file)));
+        }
+        res.push((folder.join(format!("lib{}.so", self.name)), soname.into()));
+        res
+    }
+
+    #[inline]
+    fn has_versioned_name(&self) -> bool {
+        self.version.is_some() && !cfg!(target_os = "windows") && !cfg!(target_os = "macos")
+    }
+
+    #[inline]
+    pub fn is_library(&self) -> bool {
+        self.kind != TargetKind::Executable
+    }
+
+    #[inline]
+    pub fn object_of(&self, source: &Path) -> PathBuf {
+        FilesystemManager::src_to_obj(source, &self.obj_dir)
+    }
+
+    pub fn objects(&self) -> Vec<PathBuf> {
+        self.sources.iter().map(|s| self.object_of(s)).collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::path::PathBuf;
+
+    use super::BuildTarget;
+    use crate::{
+        parsing::config::{TargetConfig, TargetKind},
+        test_utils::MockFactory,
+    };
+
+    #[test]
+    fn target_outputs_and_objects() {
+        let cfg = MockFactory::mock_cfg_default();
+        let target = TargetConfig {
+            name: "core".into(),
+            kind: TargetKind::StaticLib,
+            ..Default::default()
+        };
+        let mut core = BuildTarget::declared(&cfg, "debug", &target);
+        core.sources = vec!["src/core/a.cpp".into()];
+
+        assert_eq!(
+            core.objects(),
+            vec![PathBuf::from("target/obj/debug/core/src/core/a.cpp.o")]
+        );
+        assert!(core.is_library());
+        core.make_pic();
+        assert_eq!(
+            core.object_of("src/core/a.cpp".as_ref()),
+            PathBuf::from("target/obj/debug/core/pic/src/core/a.cpp.o")
+        );
+        assert_eq!(core.base_obj_dir(), PathBuf::from("target/obj/debug/core"));
+
+        if cfg!(target_os = "linux") {
+            let plugin = TargetConfig {
+                name: "plug".into(),
+                kind: TargetKind::SharedLib,
+                version: Some("1.2.0".into()),
+                ..Default::default()
+            };
+            let plugin = BuildTarget::declared(&cfg, "debug", &plugin);
+            assert_eq!(
+                plugin.output,
+                PathBuf::from("target/debug/libplug.so.1.2.0")
+            );
+            assert_eq!(plugin.soname(), Some("libplug.so.1".into()));
+            assert_eq!(
+                plugin.symlinks(),
+                vec![
+                    (
+                        PathBuf::from("target/debug/libplug.so.1"),
+                        PathBuf::from("libplug.so.1.2.0")
+                    ),
+                    (
+                        PathBuf::from("target/debug/libplug.so"),
+                        PathBuf::from("libplug.so.1")
+                    ),
+                ]
+            );
+            assert_eq!(core.output, PathBuf::from("target/debug/libcore.a"));
+            assert_eq!(
+                BuildTarget::implicit(&cfg, "release", TargetKind::Executable).output,
+                PathBuf::from("target/release/program")
+            );
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+
+use crate::core::Context;
+
+/// What project gives to projects using it: include directories, compile definitions and libraries to link with.
+/// Pathes are absolute, as dependents are built from their own directories.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Exports {
+    pub include_dirs: Vec<PathBuf>,
+    pub defines: Vec<String>,
+    /// Dependents go before their dependencies, as linker expects.
+    pub libs: Vec<PathBuf>,
+}
+
+impl Exports {
+    /// Exports of project built in 'ctx' from 'root' directory:
+    /// its 'export.include_dirs', 'export.defines' and built libraries, followed by what it imports itself.
+    pub fn of(ctx: &Context, root: &Path) -> Self {
+        let cfg = &ctx.config;
+        let mut res = Self {
+            include_dirs: cfg
+                .export
+                .include_dirs
+                .iter()
+                .map(|d| root.join(d))
+                .collect(),
+            defines: cfg.export.defines.clone(),
+            ..Default::default()
+        };
+
+        let order: Vec<&str> = match cfg.targets_in_order() {
+            Ok(order) if !order.is_empty() => order.iter().rev().map(|t| t.name.as_str()).collect(),
+            _ => vec![cfg.target_name.as_str()],
+        };
+        let order = order
+            .into_iter()
+            .chain(ctx.vendored.iter().map(|v| v.name.as_str()));
+        res.libs = order
+            .filter_map(|name| ctx.targets.get(name))
+            .filter(|t| t.is_library() && !t.sources.is_empty())
+            .map(|t| root.join(&t.output))
+            .collect();
+
+        res.extend(&ctx.imports);
+        res
+    }
+
+    /// Appends pathes of other exports which are not here yet.
+    pub fn extend(&mut self, other: &Exports) {
+        for dir in &other.include_dirs {
+            if !self.include_dirs.contains(dir) {
+                self.include_dirs.push(dir.clone());
+            }
+        }
+        for define in &other.defines {
+            if !self.defines.contains(define) {
+                self.defines.push(define.clone());
+            }
+        }
+        for lib in &other.libs {
+            if !self.libs.contains(lib) {
+                self.libs.push(lib.clone());
+            }
+        }
+    }
+
+    /// Directories shared libraries are loaded from at runtime.
+    pub fn shared_lib_dirs(&self) -> Vec<PathBuf> {
+        let mut res: Vec<PathBuf> = Vec::default();
+        for lib in &self.libs {
+            // Static libraries are '.a' or '.lib', versioned ones end with version.
+            let is_shared = lib.extension().is_some_and(|e| e != "a" && e != "lib");
+            let Some(dir) = lib.parent().filter(|_| is_shared) else {
+                continue;
+            };
+            if !res.iter().any(|d| d == dir) {
+                res.push(dir.to_path_buf());
+            }
+        }
+        res
+    }
+}
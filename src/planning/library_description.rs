@@ -0,0 +1,221 @@
+use std::path::PathBuf;
+
+use crate::parsing::config::TargetKind;
+
+/// Installed library as seen by consumers which are not built with cum:
+/// pkg-config file and CMake package config, both relative to installation prefix.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LibraryDescription {
+    pub name: String,
+    pub kind: TargetKind,
+    pub version: String,
+    /// File name inside of <prefix>/lib.
+    pub file: String,
+    pub soname: Option<String>,
+    /// 'export.defines': 'NAME' or 'NAME=value'.
+    pub defines: Vec<String>,
+    /// Libraries of the same project this one is linked with.
+    pub deps: Vec<String>,
+    /// Archives of fetched dependencies installed next to static library, file names inside of <prefix>/lib.
+    pub vendored: Vec<String>,
+    /// pkg-config packages, with optional version constraint.
+    pub packages: Vec<String>,
+    /// System libraries, linked with -l.
+    pub system_libs: Vec<String>,
+}
+
+impl LibraryDescription {
+    /// Installed into <prefix>/lib/pkgconfig/<name>.pc
+    pub fn pc_path(&self) -> PathBuf {
+        PathBuf::from("lib/pkgconfig").join(format!("{}.pc", self.name))
+    }
+
+    /// Installed into <prefix>/lib/cmake/<name>/<name>Config.cmake
+    pub fn cmake_path(&self) -> PathBuf {
+        PathBuf::from("lib/cmake")
+            .join(&self.name)
+            .join(format!("{}Config.cmake", self.name))
+    }
+
+    /// Static library needs everything it is linked with, shared one needs it only for static linkage.
+    #[inline]
+    fn is_static(&self) -> bool {
+        self.kind == TargetKind::StaticLib
+    }
+
+    /// Prefix is found relative to file itself, so installation can be moved (DESTDIR, packages).
+    pub fn to_pc(&self) -> String {
+        let mut res = String::from("prefix=${pcfiledir}/../..\n");
+        res.push_str("includedir=${prefix}/include\n");
+        res.push_str("libdir=${prefix}/lib\n\n");
+        res.push_str(&format!("Name: {}\n", self.name));
+        res.push_str(&format!("Description: {} library\n", self.name));
+        res.push_str(&format!("Version: {}\n", self.version));
+
+        let suffix = if self.is_static() { "" } else { ".private" };
+        if !self.packages.is_empty() {
+            res.push_str(&format!("Requires{suffix}: {}\n", self.packages.join(", ")));
+        }
+        let mut cflags = vec!["-I${includedir}".to_string()];
+        cflags.extend(self.defines.iter().map(|d| format!("-D{d}")));
+        res.push_str(&format!("Cflags: {}\n", cflags.join(" ")));
+
+        let private: Vec<String> = self
+            .deps
+            .iter()
+            .map(|l| format!("-l{l}"))
+            .chain(self.vendored.iter().map(|f| format!("${{libdir}}/{f}")))
+            .chain(self.system_libs.iter().map(|l| format!("-l{l}")))
+            .collect();
+        let mut libs = vec!["-L${libdir}".to_string(), format!("-l{}", self.name)];
+        if self.is_static() {
+            libs.extend(private);
+        } else if !private.is_empty() {
+            res.push_str(&format!("Libs.private: {}\n", private.join(" ")));
+        }
+        res.push_str(&format!("Libs: {}\n", libs.join(" ")));
+        res
+    }
+
+    /// Defines imported target <name>::<name>, libraries of the same project are
+    /// included from their own configs next to this one.
+    pub fn to_cmake(&self) -> String {
+        let target = format!("{0}::{0}", self.name);
+        let mut res = format!("# {} {}\n", self.name, self.version);
+        res.push_str(&format!(
+            "set({}_VERSION \"{}\")\n",
+            self.name, self.version
+        ));
+
+        let mut links: Vec<String> = Vec::default();
+        if self.is_static() && !(self.deps.is_empty() && self.packages.is_empty()) {
+            res.push('\n');
+        }
+        if self.is_static() {
+            for dep in &self.deps {
+                res.push_str(&format!(
+                    "include(\"${{CMAKE_CURRENT_LIST_DIR}}/../{dep}/{dep}Config.cmake\")\n"
+                ));
+                links.push(format!("{dep}::{dep}"));
+            }
+            links.extend(
+                self.vendored
+                    .iter()
+                    .map(|f| format!("${{_cum_prefix}}/lib/{f}")),
+            );
+            if !self.packages.is_empty() {
+                // Version constraint is written without spaces: 'sdl2>=2.26'
+                let modules: Vec<String> = self
+                    .packages
+                    .iter()
+                    .map(|p| p.split_whitespace().collect())
+                    .collect();
+                res.push_str("find_package(PkgConfig REQUIRED)\n");
+                res.push_str(&format!(
+                    "pkg_check_modules(_cum_{} REQUIRED IMPORTED_TARGET {})\n",
+                    self.name,
+                    modules.join(" ")
+                ));
+                links.push(format!("PkgConfig::_cum_{}", self.name));
+            }
+            links.extend(self.system_libs.iter().cloned());
+        }
+
+        let kind = if self.is_static() { "STATIC" } else { "SHARED" };
+        // Computed after included configs, which unset it.
+        res.push_str(
+            "\nget_filename_component(_cum_prefix \"${CMAKE_CURRENT_LIST_DIR}/../../..\" ABSOLUTE)\n",
+        );
+        res.push_str(&format!("if(NOT TARGET {target})\n"));
+        res.push_str(&format!("  add_library({target} {kind} IMPORTED)\n"));
+        res.push_str(&format!("  set_target_properties({target} PROPERTIES\n"));
+        res.push_str(&format!(
+            "    IMPORTED_LOCATION \"${{_cum_prefix}}/lib/{}\"\n",
+            self.file
+        ));
+        if let Some(soname) = self.soname.as_ref().filter(|_| !self.is_static()) {
+            res.push_str(&format!("    IMPORTED_SONAME \"{soname}\"\n"));
+        }
+        res.push_str("    INTERFACE_INCLUDE_DIRECTORIES \"${_cum_prefix}/include\"");
+        if !self.defines.is_empty() {
+            res.push_str(&format!(
+                "\n    INTERFACE_COMPILE_DEFINITIONS \"{}\"",
+                self.defines.join(";")
+            ));
+        }
+        if !links.is_empty() {
+            res.push_str(&format!(
+                "\n    INTERFACE_LINK_LIBRARIES \"{}\"",
+                links.join(";")
+            ));
+        }
+        res.push_str(")\nendif()\n");
+        res.push_str("unset(_cum_prefix)\n");
+        res
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::parsing::config::TargetKind;
+
+    use super::LibraryDescription;
+
+    fn static_lib() -> LibraryDescription {
+        LibraryDescription {
+            name: "core".into(),
+            kind: TargetKind::StaticLib,
+            version: "1.2.0".into(),
+            file: "libcore.a".into(),
+            defines: vec!["CORE_STATIC".into(), "CORE_LEVEL=2".into()],
+            deps: vec!["base".into()],
+            vendored: vec!["libfmt.a".into()],
+            packages: vec!["zlib >= 1.2".into()],
+            system_libs: vec!["pthread".into()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn static_lib_pc_lists_everything_in_libs() {
+        let pc = static_lib().to_pc();
+        assert!(pc.starts_with("prefix=${pcfiledir}/../..\n"));
+        assert!(pc.contains("Requires: zlib >= 1.2\n"));
+        assert!(pc.contains("Cflags: -I${includedir} -DCORE_STATIC -DCORE_LEVEL=2\n"));
+        assert!(pc.contains("Libs: -L${libdir} -lcore -lbase ${libdir}/libfmt.a -lpthread\n"));
+        assert!(!pc.contains("Libs.private"));
+    }
+
+    #[test]
+    fn shared_lib_pc_keeps_dependencies_private() {
+        let lib = LibraryDescription {
+            kind: TargetKind::SharedLib,
+            file: "libcore.so.1.2.0".into(),
+            soname: Some("libcore.so.1".into()),
+            // Fetched dependencies are linked into shared library itself.
+            vendored: Vec::default(),
+            ..static_lib()
+        };
+        let pc = lib.to_pc();
+        assert!(pc.contains("Requires.private: zlib >= 1.2\n"));
+        assert!(pc.contains("Libs.private: -lbase -lpthread\n"));
+        assert!(pc.contains("Libs: -L${libdir} -lcore\n"));
+
+        let cmake = lib.to_cmake();
+        assert!(cmake.contains("add_library(core::core SHARED IMPORTED)"));
+        assert!(cmake.contains("IMPORTED_LOCATION \"${_cum_prefix}/lib/libcore.so.1.2.0\""));
+        assert!(cmake.contains("IMPORTED_SONAME \"libcore.so.1\""));
+        assert!(!cmake.contains("INTERFACE_LINK_LIBRARIES"));
+    }
+
+    #[test]
+    fn static_lib_cmake_links_dependencies() {
+        let cmake = static_lib().to_cmake();
+        assert!(cmake.contains("include(\"${CMAKE_CURRENT_LIST_DIR}/../base/baseConfig.cmake\")"));
+        assert!(cmake.contains("pkg_check_modules(_cum_core REQUIRED IMPORTED_TARGET zlib>=1.2)"));
+        assert!(cmake.contains("INTERFACE_COMPILE_DEFINITIONS \"CORE_STATIC;CORE_LEVEL=2\""));
+        assert!(
+            cmake.contains("INTERFACE_LINK_LIBRARIES \"base::base;${_cum_prefix}/lib/libfmt.a;PkgConfig::_cum_core;pthread\"")
+        );
+    }
+}
@@ -1,4 +1,7 @@
 pub mod args_specification;
+pub mod build_target;
+pub mod exports;
+pub mod library_description;
 pub mod plan;
 pub mod planner;
 pub mod step;
@@ -4,12 +4,20 @@ use super::step::Step;
 
 pub trait PlanVisitor {
     fn visit_compilation(&self, step: &Step);
+    fn visit_module_interface(&self, step: &Step);
+    fn visit_precompiled_header(&self, step: &Step);
     fn visit_linkage(&self, step: &Step);
+    fn visit_archive(&self, step: &Step);
     fn visit_run(&self, step: &Step);
     fn visit_make_dir(&self, step: &Step);
     fn visit_remove_dir(&self, step: &Step);
     fn visit_make_file(&self, step: &Step);
     fn visit_remove_file(&self, step: &Step);
+    fn visit_make_symlink(&self, step: &Step);
+    fn visit_copy_file(&self, step: &Step);
+    fn visit_write_file(&self, step: &Step);
+    fn visit_tarball(&self, step: &Step);
+    fn visit_checksum(&self, step: &Step);
 }
 
 #[derive(Default, Debug)]
@@ -18,24 +26,80 @@ pub struct Plan {
 }
 
 impl Plan {
-    pub fn add_compilation(&mut self, source: PathBuf, output: PathBuf, preset: String) {
+    pub fn add_compilation(
+        &mut self,
+        source: PathBuf,
+        output: PathBuf,
+        preset: String,
+        target: String,
+    ) {
         self.steps.push(Step::Compilation {
             source,
             output,
             preset,
+            target,
         });
     }
 
-    pub fn add_linkage(&mut self, source: Vec<PathBuf>, output: PathBuf, preset: String) {
+    pub fn add_module_interface(
+        &mut self,
+        source: PathBuf,
+        output: PathBuf,
+        preset: String,
+        target: String,
+    ) {
+        self.steps.push(Step::ModuleInterface {
+            source,
+            output,
+            preset,
+            target,
+        });
+    }
+
+    pub fn add_precompiled_header(
+        &mut self,
+        header: PathBuf,
+        output: PathBuf,
+        preset: String,
+        target: String,
+    ) {
+        self.steps.push(Step::PrecompiledHeader {
+            header,
+            output,
+            preset,
+            target,
+        });
+    }
+
+    pub fn add_linkage(
+        &mut self,
+        source: Vec<PathBuf>,
+        output: PathBuf,
+        preset: String,
+        target: String,
+    ) {
         self.steps.push(Step::Linkage {
             source,
             output,
             preset,
+            target,
         });
     }
 
-    pub fn add_run(&mut self, exe: String, args: Vec<String>) {
-        self.steps.push(Step::Run { exe, args });
+    pub fn add_archive(&mut self, source: Vec<PathBuf>, output: PathBuf, target: String) {
+        self.steps.push(Step::Archive {
+            source,
+            output,
+            target,
+        });
+    }
+
+    pub fn add_run(&mut self, exe: String, args: Vec<String>, lib_dirs: Vec<PathBuf>) {
+        self.steps.push(Step::Run {
+            exe,
+            args,
+            lib_dirs,
+        });
     }
 
     pub fn add_make_dir(&mut self, path: PathBuf) {
@@ -54,6 +118,26 @@ impl Plan {
         self.steps.push(Step::RemoveFile { path });
     }
 
+    pub fn add_make_symlink(&mut self, path: PathBuf, original: PathBuf) {
+        self.steps.push(Step::CreateSymlink { path, original });
+    }
+
+    pub fn add_copy_file(&mut self, source: PathBuf, path: PathBuf) {
+        self.steps.push(Step::CopyFile { source, path });
+    }
+
+    pub fn add_write_file(&mut self, path: PathBuf, content: String) {
+        self.steps.push(Step::WriteFile { path, content });
+    }
+
+    pub fn add_tarball(&mut self, source: PathBuf, output: PathBuf) {
+        self.steps.push(Step::Tarball { source, output });
+    }
+
+    pub fn add_checksum(&mut self, source: PathBuf, output: PathBuf) {
+        self.steps.push(Step::Checksum { source, output });
+    }
+
     pub fn steps(&self) -> &Vec<Step> {
         &self.steps
     }
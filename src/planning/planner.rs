@@ -1,39 +1,93 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    env, fs, iter, mem,
+    path::{Component, Path, PathBuf},
+};
 
 use path_clean::PathClean;
 
 use crate::{
-    core::{Context, FilesystemManagerCell},
-    drivers::{dependency_analyzer::DependencyAnalyzer, fs_manager::FilesystemManager},
-    errors::{PlannerError, QueryError},
+    core::{Context, DiagnosticsCell, FilesystemManagerCell},
+    drivers::{
+        build_database::{BuildDatabase, BUILD_DB_PATH},
+        compilation_database::{CompilationDatabase, CompileCommand, COMPDB_PATH},
+        dependency_analyzer::{DependencyAnalyzer, MakefileParser},
+        fs_manager::{FilesystemManager, OBJ_ROOT},
+        language::Language,
+        module_graph::ModuleGraph,
+        object_cache::ObjectCache,
+        pkg_config::{PackageFlags, PKG_CONFIG_CACHE_PATH},
+        proc_spawner::ProcSpawner,
+        vendor::VENDOR_DIR,
+    },
+    errors::{ParsingError, PlannerError, QueryError},
+    execution::PlanExecutor,
     logger::Logger,
+    meta::{ARCHIVER, COMPILER, FALLBACK_ARCHIVER, TAR},
+    parsing::config::{TargetConfig, TargetKind},
+};
+
+use super::{
+    args_specification::*,
+    build_target::{BuildTarget, IMPLICIT_DIR, PIC_DIR},
+    library_description::LibraryDescription,
+    step::Step,
 };
 
-use super::{args_specification::*, step::Step};
+/// Every path installed by 'cum install', so 'cum uninstall' can remove them.
+/// Kept out of target folders, so it outlives 'cum clean'.
+pub const INSTALL_MANIFEST_PATH: &str = "install_manifest.txt";
+/// Staged directories and archives of 'cum package'.
+pub const PACKAGE_DIR: &str = "target/package";
+
+/// Source and destination of copied file, or symlink and its original.
+type PathPair = (PathBuf, PathBuf);
 
 pub struct Planner<'a> {
     ctx: &'a mut Context,
     fs_m: FilesystemManagerCell,
+    diagnostics: DiagnosticsCell,
 
     entry_point: PathBuf,
-    obj_list: Vec<PathBuf>,
     preset: String,
+    /// Names of targets being built, every target goes after its dependencies.
+    targets: Vec<String>,
+    executable: PathBuf,
 }
 
 impl<'a> Planner<'a> {
-    pub fn new(ctx: &'a mut Context, fs_m: FilesystemManagerCell) -> Self {
+    pub fn new(
+        ctx: &'a mut Context,
+        fs_m: FilesystemManagerCell,
+        diagnostics: DiagnosticsCell,
+    ) -> Self {
         Self {
             ctx,
             fs_m,
+            diagnostics,
             entry_point: PathBuf::default(),
             preset: String::default(),
-            obj_list: Vec::default(),
+            targets: Vec::default(),
+            executable: PathBuf::default(),
         }
     }
 
     pub fn try_make_plan(&mut self) -> Result<(), PlannerError> {
         self.set_preset()?;
-        self.set_entry_point()?;
+        // Unnamed params of other commands are not entry points,
+        // declared targets have entry points of their own.
+        if IncrementalBuild
+            .or(FullBuild)
+            .or(IncrementalRun)
+            .or(FullRun)
+            .or(Install)
+            .or(Package)
+            .or(CompDb)
+            .is_satisfied_by(&self.ctx.args)
+            && self.ctx.config.targets.is_empty()
+        {
+            self.set_entry_point()?;
+        }
 
         if RunTest.is_satisfied_by(&self.ctx.args) {
             self.try_set_preset_to("test".into())?;
@@ -46,14 +100,33 @@ impl<'a> Planner<'a> {
             .is_satisfied_by(&self.ctx.args)
         {
             Logger::info("Analyzing dependencies...");
-            self.plan_compilation(true)?;
-            self.plan_linkage();
+            self.plan_build(true)?;
         } else if FullBuild.or(FullRun).is_satisfied_by(&self.ctx.args) {
-            self.plan_compilation(false)?;
-            self.plan_linkage();
+            self.plan_build(false)?;
+        } else if Install.or(Package).is_satisfied_by(&self.ctx.args) {
+            let force = self.ctx.args.have_flag("force") || self.ctx.args.have_flag("f");
+            if !force {
+                Logger::info("Analyzing dependencies...");
+            }
+            self.plan_build(!force)?;
+            if Install.is_satisfied_by(&self.ctx.args) {
+                self.plan_install();
+            } else {
+                self.plan_package()?;
+            }
+        } else if CompDb.is_satisfied_by(&self.ctx.args) {
+            self.plan_compdb()?;
+        } else if Uninstall.is_satisfied_by(&self.ctx.args) {
+            self.plan_uninstall();
         } else if InitProject.is_satisfied_by(&self.ctx.args) {
             Logger::info("Initializiing empty project...");
             self.plan_init();
+        } else if Clean.is_satisfied_by(&self.ctx.args) {
+            self.plan_clean();
+        } else if CacheStats.is_satisfied_by(&self.ctx.args) {
+            self.print_cache_stats();
+        } else if CacheClear.is_satisfied_by(&self.ctx.args) {
+            self.plan_cache_clear();
         } else if let Some(cmd) = &self.ctx.args.command.as_ref() {
             return Err(QueryError::UnknownCommand(cmd.to_string()).into());
         }
@@ -69,63 +142,690 @@ impl<'a> Planner<'a> {
         Ok(())
     }
 
-    fn plan_compilation(&mut self, mut incremental: bool) -> Result<(), PlannerError> {
-        let obj_files = self
-            .fs_m
-            .find_all_with_extension("o", &PathBuf::from("target/obj"));
-        let mut src_files = self
-            .fs_m
-            .find_all_with_extension("cpp", &PathBuf::from("src"));
+    /// Sources of every target are compiled first, so compilers of all targets run
+    /// in parallel, then targets are linked after their dependencies.
+    fn plan_build(&mut self, incremental: bool) -> Result<(), PlannerError> {
+        self.ctx.build_db = BuildDatabase::load(Path::new(BUILD_DB_PATH));
+        self.detect_compiler_version();
+        self.ctx.packages = PackageFlags::query(
+            &self.ctx.config.pkg_config,
+            Path::new(PKG_CONFIG_CACHE_PATH),
+            !DryRun.is_satisfied_by(&self.ctx.args),
+        )?;
+        self.resolve_targets()?;
+        if self
+            .ctx
+            .targets
+            .values()
+            .any(|t| t.kind == TargetKind::StaticLib)
+        {
+            self.detect_archiver();
+        }
+
+        if self.ctx.targets.values().all(|t| t.sources.is_empty()) {
+            Logger::info("No source files found");
+            return Ok(());
+        }
+        self.plan_stale_cleanup();
+
+        let targets = self.targets.clone();
+        for name in &targets {
+            self.plan_compilation(name, incremental)?;
+        }
+        for name in &targets {
+            self.plan_linkage(name);
+        }
+        self.executable = self.runnable_target();
+        self.plan_compilation_database();
+
+        Ok(())
+    }
+
+    /// Compilation database is written without compiling anything.
+    fn plan_compdb(&mut self) -> Result<(), PlannerError> {
+        self.ctx.packages = PackageFlags::query(
+            &self.ctx.config.pkg_config,
+            Path::new(PKG_CONFIG_CACHE_PATH),
+            !DryRun.is_satisfied_by(&self.ctx.args),
+        )?;
+        self.resolve_targets()?;
+        self.plan_compilation_database();
+        if self.ctx.plan.steps().is_empty() {
+            Logger::info(&format!("{COMPDB_PATH} is up to date"));
+        }
+        Ok(())
+    }
+
+    /// Every source of every target being built, with arguments it is (or would be) compiled with.
+    /// Database is only written when it changes.
+    fn plan_compilation_database(&mut self) {
+        let mut commands = Vec::default();
+        for name in &self.targets {
+            let target = &self.ctx.targets[name];
+            for source in &target.sources {
+                let step = self.compilation_step(name, source);
+                let Some(args) = PlanExecutor::full_cargs(self.ctx, &step) else {
+                    continue;
+                };
+                commands.push(CompileCommand {
+                    file: source.clone(),
+                    output: target.object_of(source),
+                    arguments: iter::once(Language::of(source).compiler().to_string())
+                        .chain(args)
+                        .collect(),
+                });
+            }
+        }
+        let content = CompilationDatabase {
+            directory: self.fs_m.root().clone(),
+            commands,
+        }
+        .to_json();
+        if fs::read_to_string(COMPDB_PATH).ok().as_ref() != Some(&content) {
+            self.ctx.plan.add_write_file(COMPDB_PATH.into(), content);
+        }
+    }
+
+    #[inline]
+    fn compilation_step(&self, target: &str, source: &Path) -> Step {
+        Step::Compilation {
+            source: source.to_path_buf(),
+            output: self.ctx.targets[target].object_of(source),
+            preset: self.preset.clone(),
+            target: target.into(),
+        }
+    }
+
+    /// Project without '[[targets]]' builds single 'target_name' executable,
+    /// test runner is built this way too.
+    #[inline]
+    fn is_implicit(&self) -> bool {
+        self.ctx.config.targets.is_empty() || RunTest.is_satisfied_by(&self.ctx.args)
+    }
+
+    /// Targets selected with --target (all by default) and their dependencies,
+    /// with sources, libraries and named modules of every one.
+    fn resolve_targets(&mut self) -> Result<(), PlannerError> {
+        let cfg = &self.ctx.config;
+        let selected = self.ctx.args.named_params.get("target");
+        // Entry point of one target is never compiled into another.
+        let entry_points: Vec<PathBuf> = cfg
+            .entry_points
+            .iter()
+            .chain(cfg.targets.iter().filter_map(|t| t.entry_point.as_ref()))
+            .cloned()
+            .collect();
+        let mut targets = Vec::default();
+
+        if self.is_implicit() {
+            if selected.is_some_and(|name| *name != cfg.target_name) {
+                return Err(
+                    QueryError::InvalidTarget(selected.cloned().unwrap_or_default()).into(),
+                );
+            }
+            // Library is made of everything except entry points.
+            let lib = self.ctx.args.have_flag("lib") && !RunTest.is_satisfied_by(&self.ctx.args);
+            let kind = if lib {
+                TargetKind::StaticLib
+            } else {
+                TargetKind::Executable
+            };
+            let mut target = BuildTarget::implicit(cfg, &self.preset, kind);
+            target.sources = self.fs_m.find_sources(cfg, &cfg.sources);
+            if !target.sources.is_empty() {
+                target
+                    .sources
+                    .retain(|p| !entry_points.contains(&p.clean()));
+                if !lib {
+                    target.sources.push(self.entry_point.clone());
+                }
+            }
+            targets.push(target);
+        } else {
+            if self.ctx.args.have_flag("lib") {
+                return Err(QueryError::LibWithTargets.into());
+            }
+            let order = cfg.targets_in_order()?;
+            let built: Vec<&TargetConfig> = match selected {
+                Some(name) => {
+                    let Some(target) = order.iter().find(|t| &t.name == name) else {
+                        return Err(QueryError::InvalidTarget(name.clone()).into());
+                    };
+                    let deps = Self::dependencies_of(&order, target);
+                    order
+                        .iter()
+                        .filter(|t| t.name == *name || deps.iter().any(|d| d.name == t.name))
+                        .copied()
+                        .collect()
+                }
+                None => order.clone(),
+            };
+
+            let pic = Self::position_independent(&order);
+
+            for declared in built {
+                let mut target = BuildTarget::declared(cfg, &self.preset, declared);
+                if pic.contains(declared.name.as_str()) {
+                    target.make_pic();
+                }
+                target.sources = self.fs_m.find_sources(cfg, &declared.sources);
+                target
+                    .sources
+                    .retain(|p| !entry_points.contains(&p.clean()));
+                target.sources.extend(declared.entry_point.clone());
+
+                let deps = Self::dependencies_of(&order, declared);
+                target.libs = deps
+                    .iter()
+                    .map(|d| BuildTarget::declared(cfg, &self.preset, d).output)
+                    .collect();
+                target.rpath = deps.iter().any(|d| d.kind == TargetKind::SharedLib);
+                targets.push(target);
+            }
+        }
+
+        // Fetched dependencies are archived before targets using them are linked,
+        // position independent if some shared library is built.
+        let pic = targets.iter().any(|t| t.kind == TargetKind::SharedLib);
+        let mut vendored = Vec::default();
+        for source in &self.ctx.vendored {
+            let mut target = BuildTarget::vendored(cfg, &self.preset, source);
+            if pic {
+                target.make_pic();
+            }
+            let fs_m = FilesystemManager::new(source.root.clone()).map_err(ParsingError::from)?;
+            target.sources = fs_m
+                .find_sources(cfg, &source.sources)
+                .into_iter()
+                .map(|s| source.root.join(s))
+                .collect();
+            vendored.push(target);
+        }
+        let vendored_libs: Vec<PathBuf> = vendored.iter().map(|t| t.output.clone()).collect();
+        targets.splice(0..0, vendored);
+
+        self.targets = targets.iter().map(|t| t.name.clone()).collect();
+        self.ctx.targets.clear();
+        for mut target in targets {
+            // Libraries of other projects are linked into executables and shared libraries,
+            // static ones are passed on to dependents with Exports.
+            if target.kind != TargetKind::StaticLib {
+                target.libs.extend(vendored_libs.iter().cloned());
+                target.libs.extend(self.ctx.imports.libs.iter().cloned());
+            }
+            target.modules = ModuleGraph::scan(&target.sources)?;
+            self.ctx.targets.insert(target.name.clone(), target);
+        }
+        Ok(())
+    }
+
+    /// Libraries target depends on (including indirectly), dependents go before dependencies.
+    /// Order has to be topological, as returned by Config::targets_in_order().
+    fn dependencies_of<'c>(
+        order: &[&'c TargetConfig],
+        target: &'c TargetConfig,
+    ) -> Vec<&'c TargetConfig> {
+        let mut needed: HashSet<&str> = target.deps.iter().map(String::as_str).collect();
+        let mut res = Vec::default();
+
+        for declared in order.iter().rev() {
+            if needed.contains(declared.name.as_str()) {
+                needed.extend(declared.deps.iter().map(String::as_str));
+                res.push(*declared);
+            }
+        }
+        res
+    }
+
+    /// Shared libraries and static libraries linked into them, whether they are built or not,
+    /// so objects don't depend on selected target.
+    fn position_independent<'c>(order: &[&'c TargetConfig]) -> HashSet<&'c str> {
+        let mut res = HashSet::default();
+        for shared in order.iter().filter(|t| t.kind == TargetKind::SharedLib) {
+            res.insert(shared.name.as_str());
+            res.extend(
+                Self::dependencies_of(order, shared)
+                    .iter()
+                    .filter(|d| d.kind == TargetKind::StaticLib)
+                    .map(|d| d.name.as_str()),
+            );
+        }
+        res
+    }
+
+    /// Executable selected with --target, first built executable otherwise.
+    fn runnable_target(&self) -> PathBuf {
+        let selected = self.ctx.args.named_params.get("target");
+        self.targets
+            .iter()
+            .filter(|name| selected.is_none_or(|s| s == *name))
+            .filter_map(|name| self.ctx.targets.get(name))
+            .find(|t| t.kind == TargetKind::Executable && !t.sources.is_empty())
+            .map(|t| t.output.clone())
+            .unwrap_or_default()
+    }
+
+    fn plan_compilation(&mut self, name: &str, mut incremental: bool) -> Result<(), PlannerError> {
+        let target = &self.ctx.targets[name];
+        let obj_dir = target.obj_dir.clone();
+        let mut src_files = target.sources.clone();
 
         if src_files.is_empty() {
-            Logger::info("No .cpp files found");
             return Ok(());
         }
-        if obj_files.is_empty() {
+        if self.fs_m.find_all_with_extension("o", &obj_dir).is_empty() {
             incremental = false;
         }
-        self.retain_entry_point(&mut src_files);
-        // Generate full list of objects to link.
-        self.create_obj_list(&src_files);
+
+        // Precompiled header and BMIs are built before translation units which use them.
+        let mut prerequisites: Vec<Step> = self
+            .precompiled_header(name, incremental)
+            .into_iter()
+            .collect();
+        prerequisites.extend(self.module_interfaces(name, incremental)?);
 
         if incremental {
-            let mut anayzer =
-                DependencyAnalyzer::new(&self.ctx.config, self.fs_m.clone(), &src_files);
-            anayzer.generate_dependencies()?;
-            // Retain .cpp files that need to be recompiled.
-            src_files = anayzer.get_dirty_src();
-        }
-
-        for file in src_files {
-            self.ctx.plan.add_compilation(
-                file.clone(),
-                FilesystemManager::src_to_obj(&file),
-                self.preset.clone(),
+            let mut db = mem::take(&mut self.ctx.build_db);
+            let mut anayzer = DependencyAnalyzer::new(
+                &self.ctx.config,
+                self.fs_m.clone(),
+                self.diagnostics.clone(),
+                &src_files,
+                &self.ctx.thread_pool,
+                &obj_dir,
             );
+            anayzer.extra_dependencies = src_files
+                .iter()
+                .map(|src| {
+                    let inputs = PlanExecutor::imported_inputs(self.ctx, src, name);
+                    (src.clone(), inputs)
+                })
+                .collect();
+            anayzer.compile_args = src_files
+                .iter()
+                .filter_map(|src| {
+                    let step = self.compilation_step(name, src);
+                    Some((src.clone(), PlanExecutor::full_cargs(self.ctx, &step)?))
+                })
+                .collect();
+            anayzer.generate_dependencies();
+            // Retain sources that need to be recompiled.
+            let dirty = anayzer.get_dirty_src(&mut db, |src| self.fingerprint_of(name, src));
+            self.ctx.build_db = db;
+
+            src_files = dirty
+                .into_iter()
+                .map(|(src, reason)| {
+                    Logger::info(&format!("Recompiling {}: {reason}", src.display()));
+                    src
+                })
+                .collect();
+        }
+
+        let objects: Vec<PathBuf> = src_files
+            .iter()
+            .map(|p| FilesystemManager::src_to_obj(p, &obj_dir))
+            .collect();
+        let precompiled = prerequisites.iter().filter_map(|s| match s {
+            Step::PrecompiledHeader { output, .. } | Step::ModuleInterface { output, .. } => {
+                Some(output)
+            }
+            _ => None,
+        });
+        // Mirrored source tree has to exist before compilers write into it.
+        let obj_dirs: BTreeSet<PathBuf> = objects
+            .iter()
+            .chain(precompiled)
+            .filter_map(|o| o.parent().map(PathBuf::from))
+            .filter(|d| !d.exists())
+            .collect();
+        for dir in obj_dirs {
+            self.ctx.plan.add_make_dir(dir);
+        }
+
+        for step in prerequisites {
+            match step {
+                Step::PrecompiledHeader {
+                    header,
+                    output,
+                    preset,
+                    target,
+                } => self
+                    .ctx
+                    .plan
+                    .add_precompiled_header(header, output, preset, target),
+                Step::ModuleInterface {
+                    source,
+                    output,
+                    preset,
+                    target,
+                } => self
+                    .ctx
+                    .plan
+                    .add_module_interface(source, output, preset, target),
+                _ => {}
+            }
+        }
+        for (file, obj) in src_files.into_iter().zip(objects) {
+            self.ctx
+                .plan
+                .add_compilation(file, obj, self.preset.clone(), name.into());
         }
 
         Ok(())
     }
 
-    fn plan_linkage(&mut self) {
-        let executable_path = self.ctx.config.presets[&self.preset].target_folder.clone();
-        let executable_name = PathBuf::from(&self.ctx.config.target_name);
+    /// Step which builds precompiled header of target if it is missing or outdated.
+    fn precompiled_header(&mut self, name: &str, incremental: bool) -> Option<Step> {
+        let header = self.ctx.config.pch.clone()?;
+        let output = PlanExecutor::pch_output(self.ctx, name)?;
+        let inputs = MakefileParser::read_depfile_of(&output).unwrap_or(vec![header.clone()]);
+        let step = Step::PrecompiledHeader {
+            header: header.clone(),
+            output: output.clone(),
+            preset: self.preset.clone(),
+            target: name.into(),
+        };
+
+        let args = PlanExecutor::full_pch_args(self.ctx, &step).unwrap_or_default();
+        self.needs_precompilation(&header, &output, &inputs, &args, incremental)
+            .then_some(step)
+    }
+
+    /// Steps which build missing or outdated BMIs, modules go after modules they import.
+    /// BMI depends on inputs of every module it imports, so changes propagate to importers.
+    fn module_interfaces(
+        &mut self,
+        name: &str,
+        incremental: bool,
+    ) -> Result<Vec<Step>, PlannerError> {
+        let target = &self.ctx.targets[name];
+        let modules: Vec<(PathBuf, PathBuf)> = target
+            .modules
+            .levels()?
+            .into_iter()
+            .flatten()
+            .map(|module| {
+                let source = target
+                    .modules
+                    .interface_of(&module)
+                    .cloned()
+                    .unwrap_or_default();
+                (
+                    source,
+                    FilesystemManager::module_to_bmi(&module, &target.obj_dir),
+                )
+            })
+            .collect();
+        let mut res = Vec::default();
+
+        for (source, output) in modules {
+            let mut inputs =
+                MakefileParser::read_depfile_of(&output).unwrap_or(vec![source.clone()]);
+            inputs.extend(PlanExecutor::imported_inputs(self.ctx, &source, name));
+            let step = Step::ModuleInterface {
+                source: source.clone(),
+                output: output.clone(),
+                preset: self.preset.clone(),
+                target: name.into(),
+            };
+
+            let args = PlanExecutor::full_bmi_args(self.ctx, &step).unwrap_or_default();
+            if self.needs_precompilation(&source, &output, &inputs, &args, incremental) {
+                res.push(step);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Everything is precompiled during full build, otherwise only missing or outdated outputs.
+    fn needs_precompilation(
+        &mut self,
+        source: &Path,
+        output: &Path,
+        inputs: &[PathBuf],
+        args: &[String],
+        incremental: bool,
+    ) -> bool {
+        if !incremental {
+            return true;
+        }
+        let fingerprint = BuildDatabase::fingerprint(&self.ctx.compiler_version, args);
+        let reason = DependencyAnalyzer::dirty_reason(
+            output,
+            inputs,
+            fingerprint,
+            &mut self.ctx.build_db,
+            &mut HashMap::default(),
+        );
+
+        if let Some(reason) = &reason {
+            Logger::info(&format!("Precompiling {}: {reason}", source.display()));
+        }
+        reason.is_some()
+    }
+
+    /// Objects and depfiles without source (deleted, renamed or left from old flat layout)
+    /// are removed from object directories of every preset. Directory of declared target
+    /// belongs to it and is checked only when it is built, vendor directory belongs to fetched
+    /// dependencies (which are always built), everything else belongs to implicit target.
+    fn plan_stale_cleanup(&mut self) {
+        let implicit = self.is_implicit();
+        let preset_dir = FilesystemManager::obj_dir(&self.preset);
+        // Pathes relative to object directory of any preset.
+        let mut expected: HashSet<PathBuf> = HashSet::default();
+        for target in self.ctx.targets.values() {
+            let base = target
+                .base_obj_dir()
+                .strip_prefix(&preset_dir)
+                .unwrap_or(Path::new(""));
+            let mut sources = target.sources.clone();
+            // Objects of other entry points are kept, they are used by other builds.
+            if implicit {
+                sources.extend_from_slice(&self.ctx.config.entry_points);
+            }
+            // Position independent objects are kept as well, target may be built both ways.
+            for dir in [base.to_path_buf(), base.join(PIC_DIR)] {
+                let outputs = sources
+                    .iter()
+                    .map(|s| FilesystemManager::src_to_obj(s, &dir))
+                    .chain(
+                        self.ctx
+                            .config
+                            .pch
+                            .iter()
+                            .map(|pch| FilesystemManager::src_to_pch(pch, &dir)),
+                    )
+                    .chain(
+                        target
+                            .modules
+                            .interfaces()
+                            .map(|(name, _)| FilesystemManager::module_to_bmi(name, &dir)),
+                    );
+                for output in outputs {
+                    expected.insert(FilesystemManager::obj_to_dep(&output));
+                    expected.insert(output);
+                }
+            }
+        }
+        // First directory tells whose object it is: implicit target, fetched dependency
+        // or declared target (names of which can't clash with others).
+        let is_built = |path: &Path| {
+            let owner = match path.components().next() {
+                Some(Component::Normal(owner)) => owner.to_string_lossy(),
+                _ => return implicit,
+            };
+            if owner == VENDOR_DIR {
+                return true;
+            }
+            if owner == IMPLICIT_DIR {
+                return implicit;
+            }
+            match self.ctx.config.targets.iter().find(|t| t.name == owner) {
+                Some(declared) => !implicit && self.ctx.targets.contains_key(&declared.name),
+                None => implicit,
+            }
+        };
 
-        let executable = if cfg!(target_os = "windows") {
-            executable_path.join(executable_name).with_extension("exe")
+        let obj_root = PathBuf::from(OBJ_ROOT);
+        // Presets never put objects directly into object root.
+        let mut stale = self.fs_m.list_files(&obj_root);
+        for dir in self.fs_m.list_dirs(&obj_root) {
+            stale.extend(self.fs_m.find_all_files(&dir).into_iter().filter(|p| {
+                p.strip_prefix(&dir)
+                    .is_ok_and(|p| is_built(p) && !expected.contains(p))
+            }));
+        }
+        stale.retain(|p| {
+            p.extension()
+                .is_some_and(|e| ["o", "d", "pch", "pcm"].iter().any(|ext| e == *ext))
+        });
+
+        for file in stale {
+            Logger::info(&format!("Removing stale {}", file.display()));
+            self.ctx.plan.add_remove_file(file);
+        }
+    }
+
+    /// Fingerprint of the command line source would be compiled with.
+    fn fingerprint_of(&self, name: &str, src: &Path) -> String {
+        let step = Step::Compilation {
+            source: src.into(),
+            output: self.ctx.targets[name].object_of(src),
+            preset: self.preset.clone(),
+            target: name.into(),
+        };
+        let args = PlanExecutor::full_cargs(self.ctx, &step).unwrap_or_default();
+        BuildDatabase::fingerprint(&self.ctx.compiler_version, &args)
+    }
+
+    fn detect_compiler_version(&mut self) {
+        self.ctx.compiler_version = ProcSpawner::spawn_and_wait(COMPILER, &["--version".into()])
+            .map(|o| o.outs.trim().to_string())
+            .unwrap_or_default();
+    }
+
+    /// llvm-ar is preferred, both support deterministic mode.
+    fn detect_archiver(&mut self) {
+        let llvm_ar = ProcSpawner::spawn_and_wait(ARCHIVER, &["--version".into()]);
+        self.ctx.archiver = if llvm_ar.is_ok() {
+            ARCHIVER.into()
         } else {
-            executable_path.join(executable_name)
+            FALLBACK_ARCHIVER.into()
         };
+    }
 
-        if !executable.parent().map(Path::exists).unwrap_or(true) {
-            self.ctx
+    /// Reproducible archive flags (--sort, --mtime, --owner...) are only known to GNU tar.
+    fn check_tar() -> Result<(), PlannerError> {
+        match ProcSpawner::spawn_and_wait(TAR, &["--version".into()]) {
+            Ok(out) if out.outs.contains("GNU tar") => Ok(()),
+            _ => Err(PlannerError::NotGnuTar(TAR.into())),
+        }
+    }
+
+    /// Static library is archived, executable and shared library are linked.
+    fn plan_linkage(&mut self, name: &str) {
+        let target = &self.ctx.targets[name];
+        if target.sources.is_empty() {
+            return;
+        }
+        let output = target.output.clone();
+        let step = match target.kind {
+            TargetKind::StaticLib => Step::Archive {
+                source: target.objects(),
+                output: output.clone(),
+                target: name.into(),
+            },
+            TargetKind::Executable | TargetKind::SharedLib => Step::Linkage {
+                source: target.objects(),
+                output: output.clone(),
+                preset: self.preset.clone(),
+                target: name.into(),
+            },
+        };
+
+        if let Some(parent) = output.parent().filter(|p| !p.exists()) {
+            let planned = self
+                .ctx
                 .plan
-                .add_make_dir(executable.parent().unwrap().into());
+                .steps()
+                .iter()
+                .any(|s| matches!(s, Step::CreateDir { path } if path == parent));
+            if !planned {
+                self.ctx.plan.add_make_dir(parent.into());
+            }
         }
 
-        self.ctx
-            .plan
-            .add_linkage(self.obj_list.clone(), executable, self.preset.clone());
+        let symlinks = self.ctx.targets[name].symlinks();
+        if self.is_linked(name, &step) {
+            Logger::info(&format!("{} is up to date", output.clean().display()));
+        } else {
+            match step {
+                Step::Archive {
+                    source,
+                    output,
+                    target,
+                } => self.ctx.plan.add_archive(source, output, target),
+                Step::Linkage {
+                    source,
+                    output,
+                    preset,
+                    target,
+                } => self.ctx.plan.add_linkage(source, output, preset, target),
+                _ => {}
+            }
+        }
+
+        // Versioned shared library: libfoo.so -> libfoo.so.1 -> libfoo.so.1.2.0
+        for (path, original) in symlinks {
+            if fs::read_link(&path).ok().as_ref() != Some(&original) {
+                self.ctx.plan.add_make_symlink(path, original);
+            }
+        }
+    }
+
+    /// Linkage can be skipped if nothing of target or its libraries is rebuilt, output is newer
+    /// than every object and library, and it was produced with the same command line.
+    fn is_linked(&self, name: &str, step: &Step) -> bool {
+        let target = &self.ctx.targets[name];
+        let planned_rebuild = self.ctx.plan.steps().iter().any(|s| match s {
+            Step::Compilation { target, .. } => target == name,
+            Step::Linkage { output, .. } | Step::Archive { output, .. } => {
+                target.libs.contains(output)
+            }
+            _ => false,
+        });
+        if planned_rebuild || !target.output.exists() {
+            return false;
+        }
+
+        let mut inputs = target.objects();
+        inputs.extend_from_slice(&target.libs);
+        if target.kind != TargetKind::StaticLib {
+            inputs.extend(
+                self.ctx
+                    .config
+                    .lib_dirs
+                    .iter()
+                    .flat_map(|dir| self.fs_m.find_all_files(dir)),
+            );
+        }
+        let inputs_are_older = inputs
+            .iter()
+            .all(|p| FilesystemManager::is_newer(&target.output, p).unwrap_or(false));
+        if !inputs_are_older {
+            return false;
+        }
+
+        // Archive doesn't depend on compiler, but on archiver.
+        let fingerprint = match PlanExecutor::full_largs(self.ctx, step) {
+            Some(args) => BuildDatabase::fingerprint(&self.ctx.compiler_version, &args),
+            None => BuildDatabase::fingerprint(
+                &self.ctx.archiver,
+                &PlanExecutor::full_archive_args(step).unwrap_or_default(),
+            ),
+        };
+        self.ctx.build_db.get_link(&target.output) == Some(&fingerprint)
     }
 
     /// Default project structure is defined here.
@@ -139,22 +839,426 @@ impl<'a> Planner<'a> {
         self.ctx.plan.add_make_dir("dependencies/lib".into());
     }
 
-    fn plan_run_linked(&mut self) {
-        if let Some(Step::Linkage { output, .. }) = self.ctx.plan.steps().last().cloned() {
-            self.ctx.plan.add_run(
-                output.clean().display().to_string(),
-                self.ctx.args.freestanding_params.clone(),
-            );
+    /// Whole target directory by default, scoped by --preset and --objects-only.
+    fn plan_clean(&mut self) {
+        let objects_only = self.ctx.args.have_flag("objects-only");
+        let presets = &self.ctx.config.presets;
+
+        let mut dirs: Vec<PathBuf> = if self.ctx.args.have_flag("preset") {
+            let mut res = vec![FilesystemManager::obj_dir(&self.preset)];
+            if !objects_only {
+                res.push(presets[&self.preset].target_folder.clone());
+            }
+            res
+        } else if objects_only {
+            vec![OBJ_ROOT.into()]
+        } else {
+            iter::once(PathBuf::from("target"))
+                .chain(presets.values().map(|p| p.target_folder.clone()))
+                .collect()
+        };
+        dirs.iter_mut().for_each(|d| *d = d.clean());
+        // Misconfigured 'target_folder' must not take project (or anything outside of it) along.
+        dirs.retain(|d| {
+            let inside = d.components().all(|c| matches!(c, Component::Normal(_)))
+                && d.components().next().is_some();
+            if !inside {
+                self.diagnostics
+                    .borrow_mut()
+                    .report_error(PlannerError::OutsideOfProject(d.clone()));
+            }
+            inside
+        });
+        // Parents go first, so nested directories are removed along with them.
+        dirs.sort();
+
+        let mut removed: Vec<PathBuf> = Vec::default();
+        for dir in dirs {
+            if dir.exists() && !removed.iter().any(|r| dir.starts_with(r)) {
+                removed.push(dir);
+            }
+        }
+
+        if removed.is_empty() {
+            Logger::info("Nothing to clean");
+        }
+        for dir in removed {
+            self.ctx.plan.add_remove_dir(dir);
+        }
+    }
+
+    fn print_cache_stats(&self) {
+        let Some(cache) = ObjectCache::from_config(&self.ctx.config.cache) else {
+            Logger::warning("Object cache is disabled");
+            return;
+        };
+        let stats = cache.stats();
+        Logger::info(&format!("Object cache: {}", cache.dir().display()));
+        Logger::info(&format!("Entries: {}", stats.entries));
+        Logger::info(&format!(
+            "Size: {:.2} MB of {} MB",
+            stats.size as f64 / (1024.0 * 1024.0),
+            self.ctx.config.cache.max_size_mb.unwrap_or(0)
+        ));
+    }
+
+    /// Cache is cleared even if it is disabled in config.
+    /// Only objects are removed, cache directory may be shared with fetched sources or anything else.
+    fn plan_cache_clear(&mut self) {
+        match ObjectCache::objects_dir() {
+            Some(dir) if dir.exists() => self.ctx.plan.add_remove_dir(dir),
+            _ => Logger::info("Nothing to clean"),
+        }
+    }
+
+    /// Built executables are copied into <prefix>/bin, libraries (with their symlinks) into
+    /// <prefix>/lib, public headers into <prefix>/include. Fetched dependencies are only installed
+    /// along with static libraries, which aren't linked with them.
+    fn plan_install(&mut self) {
+        let (files, links) = self.installed_files(&self.install_root());
+        if files.is_empty() {
+            Logger::info("Nothing to install");
+            return;
+        }
+
+        for dir in Self::parent_dirs(&files)
+            .into_iter()
+            .filter(|d| !d.exists())
+        {
+            self.ctx.plan.add_make_dir(dir);
+        }
+        let root = self.install_root();
+        let mut installed = self.plan_copy_files(files, links);
+        installed.push_str(&self.plan_library_configs(&root));
+        // Directories created by install are listed too, uninstall removes them once empty.
+        let created_dirs: BTreeSet<PathBuf> = installed
+            .lines()
+            .flat_map(|p| {
+                Path::new(p)
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|d| !d.as_os_str().is_empty() && !d.exists())
+            })
+            .map(PathBuf::from)
+            .collect();
+
+        // Paths of previous installs (other prefix or preset) are kept for 'cum uninstall'.
+        let mut manifest = fs::read_to_string(INSTALL_MANIFEST_PATH).unwrap_or_default();
+        if !manifest.is_empty() && !manifest.ends_with('\n') {
+            manifest.push('\n');
+        }
+        for path in created_dirs
+            .iter()
+            .map(|d| d.display().to_string())
+            .chain(installed.lines().map(String::from))
+        {
+            if !manifest.lines().any(|l| l == path) {
+                manifest.push_str(&path);
+                manifest.push('\n');
+            }
+        }
+        self.ctx
+            .plan
+            .add_write_file(INSTALL_MANIFEST_PATH.into(), manifest);
+    }
+
+    /// Install layout is staged into target/package/<name>-<version> along with 'package.files',
+    /// which is packed into deterministic tarball with sha256 checksum next to it.
+    fn plan_package(&mut self) -> Result<(), PlannerError> {
+        Self::check_tar()?;
+        let package = &self.ctx.config.package;
+        let name = format!(
+            "{}-{}",
+            package
+                .name
+                .as_ref()
+                .unwrap_or(&self.ctx.config.target_name),
+            self.package_version()
+        );
+        let staged = PathBuf::from(PACKAGE_DIR).join(&name);
+
+        let (mut files, links) = self.installed_files(&staged);
+        for declared in &package.files {
+            if !declared.exists() {
+                return Err(PlannerError::MissingPackageFile(declared.clone()));
+            }
+            let mut extra = if declared.is_dir() {
+                self.fs_m.find_all_files(declared)
+            } else {
+                vec![declared.clone()]
+            };
+            extra.sort();
+            for file in extra {
+                let relative = declared
+                    .parent()
+                    .and_then(|p| file.strip_prefix(p).ok())
+                    .map(PathBuf::from)
+                    .unwrap_or(file.clone());
+                files.push((file, staged.join(relative)));
+            }
+        }
+
+        // Staged directory only contains files of this package.
+        if staged.exists() {
+            self.ctx.plan.add_remove_dir(staged.clone());
         }
+        let mut dirs = Self::parent_dirs(&files);
+        dirs.insert(staged.clone());
+        for dir in dirs {
+            self.ctx.plan.add_make_dir(dir);
+        }
+        self.plan_copy_files(files, links);
+        self.plan_library_configs(&staged);
+
+        let archive = PathBuf::from(PACKAGE_DIR).join(format!("{name}.tar.gz"));
+        let checksum = PathBuf::from(PACKAGE_DIR).join(format!("{name}.tar.gz.sha256"));
+        self.ctx.plan.add_tarball(staged, archive.clone());
+        self.ctx.plan.add_checksum(archive, checksum);
+        Ok(())
     }
 
-    fn retain_entry_point(&self, list: &mut Vec<PathBuf>) {
-        list.retain(|p| !self.ctx.config.entry_points.contains(&p.clean()));
-        list.push(self.entry_point.clone());
+    /// Source and destination of every installed file, and installed symlinks of libraries:
+    /// executables go to <root>/bin, libraries to <root>/lib, public headers to <root>/include.
+    /// Archives of fetched dependencies go to <root>/lib only along with static libraries.
+    fn installed_files(&self, root: &Path) -> (Vec<PathPair>, Vec<PathPair>) {
+        let mut files: Vec<PathPair> = Vec::default();
+        let mut links: Vec<PathPair> = Vec::default();
+
+        let with_vendored = self.targets.iter().any(|n| {
+            let target = &self.ctx.targets[n];
+            target.kind == TargetKind::StaticLib && !target.vendored && !target.sources.is_empty()
+        });
+        for name in &self.targets {
+            let target = &self.ctx.targets[name];
+            let Some(file) = target.output.file_name() else {
+                continue;
+            };
+            if (target.vendored && !with_vendored) || target.sources.is_empty() {
+                continue;
+            }
+            let dir = root.join(if target.is_library() { "lib" } else { "bin" });
+            files.push((target.output.clone(), dir.join(file)));
+            for (link, original) in target.symlinks() {
+                links.extend(link.file_name().map(|l| (dir.join(l), original)));
+            }
+        }
+        for (header, relative) in self.public_headers() {
+            files.push((header, root.join("include").join(relative)));
+        }
+        (files, links)
+    }
+
+    #[inline]
+    fn package_version(&self) -> &str {
+        self.ctx
+            .config
+            .package
+            .version
+            .as_deref()
+            .unwrap_or("0.1.0")
+    }
+
+    /// pkg-config file and CMake config of every installed library, returns written pathes, one per line.
+    fn plan_library_configs(&mut self, root: &Path) -> String {
+        let mut written = String::default();
+        for library in self.library_descriptions() {
+            for (path, content) in [
+                (root.join(library.pc_path()), library.to_pc()),
+                (root.join(library.cmake_path()), library.to_cmake()),
+            ] {
+                written.push_str(&format!("{}\n", path.display()));
+                self.ctx.plan.add_write_file(path, content);
+            }
+        }
+        written
     }
 
-    fn create_obj_list(&mut self, src_list: &[PathBuf]) {
-        self.obj_list = src_list.iter().map(FilesystemManager::src_to_obj).collect();
+    fn library_descriptions(&self) -> Vec<LibraryDescription> {
+        let cfg = &self.ctx.config;
+        let installed = |t: &&BuildTarget| t.is_library() && !t.vendored && !t.sources.is_empty();
+        let system_libs = cfg
+            .presets
+            .get(&self.preset)
+            .map(|p| p.libs.clone())
+            .unwrap_or_default();
+
+        let mut res = Vec::default();
+        for target in self
+            .targets
+            .iter()
+            .map(|n| &self.ctx.targets[n])
+            .filter(installed)
+        {
+            let Some(file) = target.output.file_name() else {
+                continue;
+            };
+            let deps = target
+                .libs
+                .iter()
+                .filter_map(|lib| {
+                    self.ctx
+                        .targets
+                        .values()
+                        .filter(installed)
+                        .find(|t| &t.output == lib)
+                })
+                .map(|t| t.name.clone())
+                .collect();
+            // Static library is linked with fetched dependencies by its consumers.
+            let vendored = self
+                .targets
+                .iter()
+                .map(|n| &self.ctx.targets[n])
+                .filter(|t| target.kind == TargetKind::StaticLib && t.vendored)
+                .filter(|t| !t.sources.is_empty())
+                .filter_map(|t| t.output.file_name())
+                .map(|f| f.to_string_lossy().to_string())
+                .collect();
+            res.push(LibraryDescription {
+                name: target.name.clone(),
+                kind: target.kind,
+                version: target
+                    .version
+                    .clone()
+                    .unwrap_or(self.package_version().into()),
+                file: file.to_string_lossy().to_string(),
+                soname: target.soname(),
+                defines: cfg.export.defines.clone(),
+                deps,
+                vendored,
+                packages: cfg.pkg_config.clone(),
+                system_libs: system_libs.clone(),
+            });
+        }
+        res
+    }
+
+    fn parent_dirs(files: &[PathPair]) -> BTreeSet<PathBuf> {
+        files
+            .iter()
+            .filter_map(|(_, dest)| dest.parent().map(PathBuf::from))
+            .collect()
+    }
+
+    /// Returns every created path, one per line.
+    fn plan_copy_files(&mut self, files: Vec<PathPair>, links: Vec<PathPair>) -> String {
+        let mut created = String::default();
+        for (source, dest) in files {
+            created.push_str(&format!("{}\n", dest.display()));
+            self.ctx.plan.add_copy_file(source, dest);
+        }
+        for (link, original) in links {
+            created.push_str(&format!("{}\n", link.display()));
+            self.ctx.plan.add_make_symlink(link, original);
+        }
+        created
+    }
+
+    /// $DESTDIR followed by --prefix (or 'install.prefix').
+    fn install_root(&self) -> PathBuf {
+        let prefix = self
+            .ctx
+            .args
+            .named_params
+            .get("prefix")
+            .map(PathBuf::from)
+            .or(self.ctx.config.install.prefix.clone())
+            .unwrap_or_default();
+        match env::var_os("DESTDIR").filter(|d| !d.is_empty()) {
+            Some(destdir) => PathBuf::from(destdir).join(
+                prefix
+                    .components()
+                    .filter(|c| !matches!(c, Component::RootDir | Component::Prefix(_)))
+                    .collect::<PathBuf>(),
+            ),
+            None => prefix,
+        }
+    }
+
+    /// Header files of 'install.headers' along with their pathes inside of installed include directory:
+    /// relative to include directory containing them, or to parent of declared directory.
+    fn public_headers(&self) -> Vec<(PathBuf, PathBuf)> {
+        let mut res = Vec::default();
+        for declared in &self.ctx.config.install.headers {
+            let mut headers = if declared.is_dir() {
+                self.fs_m.find_all_files(declared)
+            } else {
+                vec![declared.clone()]
+            };
+            headers.sort();
+
+            for header in headers {
+                let relative = self
+                    .ctx
+                    .config
+                    .include_dirs
+                    .iter()
+                    .find_map(|d| header.strip_prefix(d).ok())
+                    .or_else(|| header.strip_prefix(declared.parent()?).ok())
+                    .map(PathBuf::from)
+                    .unwrap_or(header.clone());
+                res.push((header, relative));
+            }
+        }
+        res
+    }
+
+    /// Every file of install manifest is removed, along with manifest itself.
+    /// Directories created by install are removed (deepest first) only if nothing else is left in them.
+    fn plan_uninstall(&mut self) {
+        let Ok(manifest) = fs::read_to_string(INSTALL_MANIFEST_PATH) else {
+            Logger::info("Nothing to uninstall");
+            return;
+        };
+        let mut dirs = Vec::default();
+        let mut removed = HashSet::new();
+        for path in manifest
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(PathBuf::from)
+        {
+            match path.symlink_metadata() {
+                Ok(meta) if meta.is_dir() => dirs.push(path),
+                Ok(_) => {
+                    removed.insert(path.clone());
+                    self.ctx.plan.add_remove_file(path);
+                }
+                Err(_) => {}
+            }
+        }
+
+        dirs.sort_by_key(|d| std::cmp::Reverse(d.components().count()));
+        for dir in dirs {
+            let emptied = fs::read_dir(&dir).is_ok_and(|mut entries| {
+                entries.all(|e| e.is_ok_and(|e| removed.contains(&e.path())))
+            });
+            if emptied {
+                removed.insert(dir.clone());
+                self.ctx.plan.add_remove_dir(dir);
+            }
+        }
+        self.ctx.plan.add_remove_file(INSTALL_MANIFEST_PATH.into());
+    }
+
+    /// Shared libraries are looked for next to executable, in 'lib_dirs'
+    /// and next to shared libraries of projects this one uses.
+    fn plan_run_linked(&mut self) {
+        if self.executable != PathBuf::default() {
+            let lib_dirs = self
+                .executable
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .map(PathBuf::from)
+                .into_iter()
+                .chain(self.ctx.config.lib_dirs.iter().cloned())
+                .chain(self.ctx.imports.shared_lib_dirs())
+                .collect();
+            self.ctx.plan.add_run(
+                self.executable.clean().display().to_string(),
+                self.ctx.args.freestanding_params.clone(),
+                lib_dirs,
+            );
+        }
     }
 
     fn set_preset(&mut self) -> Result<(), QueryError> {
@@ -164,7 +1268,13 @@ impl<'a> Planner<'a> {
             .named_params
             .get("preset")
             .cloned()
-            .unwrap_or(String::from("debug"));
+            .unwrap_or(String::from(
+                if Install.or(Package).is_satisfied_by(&self.ctx.args) {
+                    "release"
+                } else {
+                    "debug"
+                },
+            ));
 
         if !self.ctx.config.presets.contains_key(&preset) {
             Err(QueryError::InvalidPreset(preset))
@@ -210,21 +1320,28 @@ impl<'a> Planner<'a> {
     }
 }
 
+#[cfg(test)]
 pub mod tests {
+    use std::{env, fs, path::PathBuf};
+
     use crate::{
-        core::{Context, FilesystemManagerCell},
+        core::{Context, DiagnosticsCell, FilesystemManagerCell},
+        drivers::vendor::VendoredSource,
+        errors::{ParsingError, PlannerError, QueryError},
+        execution::PlanExecutor,
+        parsing::config::{Preset, TargetConfig, TargetKind},
         test_utils::{set_dir_to_tests, MockFactory},
     };
 
-    use super::Planner;
+    use super::{Planner, Step};
 
     #[test]
     fn simple_planner_build_inc_debug() {
-        set_dir_to_tests();
+        let _dir = set_dir_to_tests();
         let fs_m = FilesystemManagerCell::default();
         let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "build"]);
 
-        let mut planner = Planner::new(&mut mock_ctx, fs_m);
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
         planner.try_make_plan().unwrap();
 
         println!("IncrementalBuild: {:#?}", mock_ctx.plan);
@@ -232,12 +1349,12 @@ pub mod tests {
 
     #[test]
     fn simple_planner_run_inc_debug() {
-        set_dir_to_tests();
+        let _dir = set_dir_to_tests();
         let fs_m = FilesystemManagerCell::default();
         let mut mock_ctx =
             MockFactory::mock_ctx_for_call(&["cum.exe", "run", "--", "--param", "-flag"]);
 
-        let mut planner = Planner::new(&mut mock_ctx, fs_m);
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
         planner.try_make_plan().unwrap();
 
         println!("IncrementalRun: {:#?}", mock_ctx.plan);
@@ -245,14 +1362,562 @@ pub mod tests {
 
     #[test]
     fn simple_planner_test_debug() {
-        set_dir_to_tests();
+        let _dir = set_dir_to_tests();
         let fs_m = FilesystemManagerCell::default();
         let mut mock_ctx =
             MockFactory::mock_ctx_for_call(&["cum.exe", "test", "--", "--param", "-flag"]);
 
-        let mut planner = Planner::new(&mut mock_ctx, fs_m);
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
         planner.try_make_plan().unwrap();
 
         println!("RunTest: {:#?}", mock_ctx.plan);
     }
+
+    #[test]
+    fn simple_planner_clean_objects() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&[
+            "cum.exe",
+            "clean",
+            "--preset=release",
+            "--objects-only",
+        ]);
+
+        fs::create_dir_all("target/obj/release").unwrap();
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        planner.try_make_plan().unwrap();
+
+        let [Step::RemoveDir { path }] = mock_ctx.plan.steps().as_slice() else {
+            panic!("Expected single removal: {:?}", mock_ctx.plan.steps());
+        };
+        assert_eq!(path.as_os_str(), "target/obj/release");
+    }
+
+    #[test]
+    fn simple_planner_clean_stays_inside_of_project() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let diagnostics = DiagnosticsCell::default();
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "clean"]);
+        for (name, folder) in [
+            ("nested", "target/nested/deeper"),
+            ("root", "."),
+            ("escape", "../out"),
+        ] {
+            let preset = Preset {
+                target_folder: folder.into(),
+                ..Default::default()
+            };
+            mock_ctx.config.presets.insert(name.into(), preset);
+        }
+        fs::create_dir_all("target/nested/deeper").unwrap();
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, diagnostics.clone());
+        planner.try_make_plan().unwrap();
+
+        // Preset folders are nested in 'target', which is removed once.
+        let [Step::RemoveDir { path }] = mock_ctx.plan.steps().as_slice() else {
+            panic!("Expected single removal: {:?}", mock_ctx.plan.steps());
+        };
+        assert_eq!(path.as_os_str(), "target");
+        assert!(diagnostics.borrow().contains_error());
+    }
+
+    #[test]
+    fn simple_planner_pch_before_compilation() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "build", "--force"]);
+        mock_ctx.config.pch = Some("include/file.hpp".into());
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        planner.try_make_plan().unwrap();
+
+        let steps = mock_ctx.plan.steps();
+        let pch = steps
+            .iter()
+            .position(|s| matches!(s, Step::PrecompiledHeader { .. }))
+            .unwrap();
+        let first_compilation = steps
+            .iter()
+            .position(|s| matches!(s, Step::Compilation { .. }))
+            .unwrap();
+        assert!(pch < first_compilation);
+    }
+
+    #[test]
+    fn simple_planner_pch_and_bmi_share_compilation_flags() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "build", "--force"]);
+        mock_ctx.config.pch = Some("include/file.hpp".into());
+        for preset in mock_ctx.config.presets.values_mut() {
+            preset
+                .lang_cflags
+                .insert("cpp".into(), vec!["-fno-exceptions".into()]);
+        }
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        planner.try_make_plan().unwrap();
+
+        let steps = mock_ctx.plan.steps();
+        let compilation = steps
+            .iter()
+            .find(|s| matches!(s, Step::Compilation { .. }))
+            .unwrap();
+        let Step::Compilation { preset, target, .. } = compilation else {
+            unreachable!();
+        };
+        let cargs = PlanExecutor::full_cargs(&mock_ctx, compilation).unwrap();
+        let pch = steps
+            .iter()
+            .find(|s| matches!(s, Step::PrecompiledHeader { .. }))
+            .and_then(|s| PlanExecutor::full_pch_args(&mock_ctx, s))
+            .unwrap();
+        let bmi = PlanExecutor::full_bmi_args(
+            &mock_ctx,
+            &Step::ModuleInterface {
+                source: "src/math.cppm".into(),
+                output: "target/obj/debug/src/math.pcm".into(),
+                preset: preset.clone(),
+                target: target.clone(),
+            },
+        )
+        .unwrap();
+
+        // Everything before precompiled header has to match, clang rejects mismatching options.
+        let shared = cargs.iter().position(|a| a == "-include-pch").unwrap();
+        assert!(cargs[..shared].contains(&"-fno-exceptions".into()));
+        assert_eq!(pch[..shared], cargs[..shared]);
+        assert_eq!(bmi[..shared], cargs[..shared]);
+    }
+
+    #[test]
+    fn simple_planner_archive_before_dependent_linkage() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "build", "--force"]);
+        mock_ctx.config.targets = vec![
+            TargetConfig {
+                name: "app".into(),
+                entry_point: Some("src/main.cpp".into()),
+                deps: vec!["core".into()],
+                ..Default::default()
+            },
+            TargetConfig {
+                name: "core".into(),
+                kind: TargetKind::StaticLib,
+                sources: vec!["src".into()],
+                ..Default::default()
+            },
+        ];
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        planner.try_make_plan().unwrap();
+
+        let steps = mock_ctx.plan.steps();
+        let archive = steps
+            .iter()
+            .position(|s| {
+                matches!(s, Step::Archive { source, .. }
+                if source == &[PathBuf::from("target/obj/debug/core/src/dep1.cpp.o")])
+            })
+            .unwrap();
+        let linkage = steps
+            .iter()
+            .position(|s| matches!(s, Step::Linkage { target, .. } if target == "app"))
+            .unwrap();
+        assert!(archive < linkage);
+        assert_eq!(
+            mock_ctx.targets["app"].libs,
+            vec![mock_ctx.targets["core"].output.clone()]
+        );
+    }
+
+    #[test]
+    fn simple_planner_stale_cleanup_by_object_owner() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        // Built by 'cum test', target named 'src' must not claim it.
+        let implicit_obj = PathBuf::from("target/obj/debug/default/src/dep1.cpp.o");
+        let stale_obj = PathBuf::from("target/obj/debug/src/src/gone.cpp.o");
+        for obj in [&implicit_obj, &stale_obj] {
+            fs::create_dir_all(obj.parent().unwrap()).unwrap();
+            fs::write(obj, "").unwrap();
+        }
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "build", "--force"]);
+        mock_ctx.config.targets = vec![
+            TargetConfig {
+                name: "src".into(),
+                kind: TargetKind::StaticLib,
+                sources: vec!["src".into()],
+                ..Default::default()
+            },
+            TargetConfig {
+                name: "app".into(),
+                entry_point: Some("src/main.cpp".into()),
+                deps: vec!["src".into()],
+                ..Default::default()
+            },
+        ];
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        let res = planner.try_make_plan();
+        for obj in [&implicit_obj, &stale_obj] {
+            fs::remove_file(obj).unwrap();
+        }
+        res.unwrap();
+
+        let removed: Vec<&PathBuf> = mock_ctx
+            .plan
+            .steps()
+            .iter()
+            .filter_map(|s| match s {
+                Step::RemoveFile { path } => Some(path),
+                _ => None,
+            })
+            .collect();
+        assert!(removed.contains(&&stale_obj));
+        assert!(!removed.contains(&&implicit_obj));
+
+        mock_ctx.config.targets[0].name = "default".into();
+        assert!(matches!(
+            mock_ctx.config.targets_in_order(),
+            Err(ParsingError::ReservedTarget(_))
+        ));
+    }
+
+    #[test]
+    fn simple_planner_lib_archives_non_entry_points() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx =
+            MockFactory::mock_ctx_for_call(&["cum.exe", "build", "--force", "--lib"]);
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        planner.try_make_plan().unwrap();
+
+        let steps = mock_ctx.plan.steps();
+        assert!(!steps.iter().any(|s| matches!(s, Step::Linkage { .. })));
+        assert!(steps
+            .iter()
+            .any(|s| matches!(s, Step::Archive { source, .. }
+            if source == &[PathBuf::from("target/obj/debug/default/src/dep1.cpp.o")])));
+        let archive = steps
+            .iter()
+            .find(|s| matches!(s, Step::Archive { .. }))
+            .unwrap();
+        assert_eq!(
+            PlanExecutor::full_archive_args(archive).unwrap()[..2],
+            ["rcsD", "target/debug/libprogram.a"]
+        );
+    }
+
+    #[test]
+    fn simple_planner_lib_with_declared_targets() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx =
+            MockFactory::mock_ctx_for_call(&["cum.exe", "build", "--force", "--lib"]);
+        mock_ctx.config.targets = vec![TargetConfig {
+            name: "core".into(),
+            kind: TargetKind::StaticLib,
+            sources: vec!["src".into()],
+            ..Default::default()
+        }];
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        assert!(matches!(
+            planner.try_make_plan(),
+            Err(PlannerError::QueryError(QueryError::LibWithTargets))
+        ));
+    }
+
+    #[test]
+    fn simple_planner_shared_lib_is_position_independent() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "build", "--force"]);
+        mock_ctx.config.targets = vec![
+            TargetConfig {
+                name: "core".into(),
+                kind: TargetKind::SharedLib,
+                sources: vec!["src".into()],
+                ..Default::default()
+            },
+            TargetConfig {
+                name: "app".into(),
+                entry_point: Some("src/main.cpp".into()),
+                deps: vec!["core".into()],
+                ..Default::default()
+            },
+        ];
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        planner.try_make_plan().unwrap();
+
+        let args_of = |name: &str| {
+            mock_ctx
+                .plan
+                .steps()
+                .iter()
+                .find(|s| matches!(s, Step::Linkage { target, .. } if target == name))
+                .and_then(|s| PlanExecutor::full_largs(&mock_ctx, s))
+                .unwrap()
+        };
+        assert!(args_of("core").contains(&"-shared".into()));
+        assert!(args_of("core").contains(&"target/obj/debug/core/pic/src/dep1.cpp.o".into()));
+        if cfg!(target_os = "linux") {
+            assert!(args_of("app").contains(&"-Wl,-rpath,$ORIGIN".into()));
+            // Installed into <prefix>/bin, next to <prefix>/lib.
+            assert!(args_of("app").contains(&"-Wl,-rpath,$ORIGIN/../lib".into()));
+        }
+        assert!(mock_ctx.targets["core"].cflags.contains(&"-fPIC".into()));
+        assert!(!mock_ctx.targets["app"].pic);
+    }
+
+    #[test]
+    fn simple_planner_links_imported_libs() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "run", "--force"]);
+        let imported = PathBuf::from("/ws/core/target/debug/libcore.so");
+        mock_ctx.imports.libs = vec![imported.clone()];
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        planner.try_make_plan().unwrap();
+
+        let steps = mock_ctx.plan.steps();
+        let largs = steps
+            .iter()
+            .find(|s| matches!(s, Step::Linkage { .. }))
+            .and_then(|s| PlanExecutor::full_largs(&mock_ctx, s))
+            .unwrap();
+        assert!(largs.contains(&imported.display().to_string()));
+        assert!(steps.iter().any(|s| matches!(s, Step::Run { lib_dirs, .. }
+            if lib_dirs.contains(&PathBuf::from("/ws/core/target/debug")))));
+    }
+
+    #[test]
+    fn simple_planner_install_to_prefix() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx =
+            MockFactory::mock_ctx_for_call(&["cum.exe", "install", "--force", "--prefix=/opt/cum"]);
+        mock_ctx.config.install.headers = vec!["include".into()];
+        let previous = "/usr/local/bin/program\n/opt/cum/bin/program\n";
+        fs::write(super::INSTALL_MANIFEST_PATH, previous).unwrap();
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        let res = planner.try_make_plan();
+        fs::remove_file(super::INSTALL_MANIFEST_PATH).unwrap();
+        res.unwrap();
+
+        let steps = mock_ctx.plan.steps();
+        assert!(steps
+            .iter()
+            .any(|s| matches!(s, Step::CopyFile { source, path }
+            if source.as_os_str() == "target/release/program"
+                && path.as_os_str() == "/opt/cum/bin/program")));
+        assert!(steps
+            .iter()
+            .any(|s| matches!(s, Step::CopyFile { source, path }
+            if source.as_os_str() == "include/file.hpp"
+                && path.as_os_str() == "/opt/cum/include/file.hpp")));
+        let Some(Step::WriteFile { path, content }) = steps.last() else {
+            panic!("Install manifest is not written last");
+        };
+        assert_eq!(path.as_os_str(), super::INSTALL_MANIFEST_PATH);
+        assert!(content.starts_with(previous), "Previous install is kept");
+        assert_eq!(
+            content
+                .lines()
+                .filter(|l| *l == "/opt/cum/bin/program")
+                .count(),
+            1
+        );
+        assert!(content.lines().any(|l| l == "/opt/cum/include/file.hpp"));
+    }
+
+    #[test]
+    fn simple_planner_install_static_lib_with_vendored() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx =
+            MockFactory::mock_ctx_for_call(&["cum.exe", "install", "--force", "--prefix=/opt/cum"]);
+        mock_ctx.config.targets = vec![TargetConfig {
+            name: "core".into(),
+            kind: TargetKind::StaticLib,
+            sources: vec!["src".into()],
+            ..Default::default()
+        }];
+        let root = env::current_dir().unwrap();
+        mock_ctx.vendored = vec![VendoredSource {
+            name: "fmt".into(),
+            sources: vec![root.join("src")],
+            root,
+            ..Default::default()
+        }];
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        planner.try_make_plan().unwrap();
+
+        let steps = mock_ctx.plan.steps();
+        assert!(steps
+            .iter()
+            .any(|s| matches!(s, Step::CopyFile { source, path }
+            if source.ends_with("libfmt.a") && path.as_os_str() == "/opt/cum/lib/libfmt.a")));
+        let pc = steps.iter().find_map(|s| match s {
+            Step::WriteFile { path, content } if path.ends_with("pkgconfig/core.pc") => {
+                Some(content)
+            }
+            _ => None,
+        });
+        assert!(pc.is_some_and(|pc| pc.contains("-lcore ${libdir}/libfmt.a")));
+    }
+
+    #[test]
+    fn simple_planner_uninstall_removes_emptied_dirs() {
+        let _dir = set_dir_to_tests();
+        let root = env::temp_dir().join(format!("cum_uninstall_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["include/lib", "lib/pkgconfig", "lib/cmake/lib"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let installed = [
+            "include/lib/api.hpp",
+            "lib/pkgconfig/lib.pc",
+            "lib/cmake/lib/libConfig.cmake",
+            "lib/liblib.a",
+        ];
+        for file in installed {
+            fs::write(root.join(file), "").unwrap();
+        }
+        // Left by someone else, so its directory is kept.
+        fs::write(root.join("lib/pkgconfig/other.pc"), "").unwrap();
+        let manifest: String = [
+            "include",
+            "include/lib",
+            "lib/pkgconfig",
+            "lib/cmake",
+            "lib/cmake/lib",
+        ]
+        .iter()
+        .chain(installed.iter())
+        .map(|p| format!("{}\n", root.join(p).display()))
+        .collect();
+        fs::write(super::INSTALL_MANIFEST_PATH, manifest).unwrap();
+
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "uninstall"]);
+        let mut planner = Planner::new(
+            &mut mock_ctx,
+            FilesystemManagerCell::default(),
+            DiagnosticsCell::default(),
+        );
+        planner.try_make_plan().unwrap();
+        fs::remove_file(super::INSTALL_MANIFEST_PATH).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let removed_dirs: Vec<PathBuf> = mock_ctx
+            .plan
+            .steps()
+            .iter()
+            .filter_map(|s| match s {
+                Step::RemoveDir { path } => path.strip_prefix(&root).ok().map(PathBuf::from),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            removed_dirs,
+            ["lib/cmake/lib", "include/lib", "lib/cmake", "include"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn simple_planner_package_ends_with_checksum() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "package", "--force"]);
+        mock_ctx.config.package.version = Some("1.0.0".into());
+        mock_ctx.config.package.files = vec!["Cum.toml".into()];
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        planner.try_make_plan().unwrap();
+
+        let staged = PathBuf::from("target/package/program-1.0.0");
+        let steps = mock_ctx.plan.steps();
+        assert!(steps
+            .iter()
+            .any(|s| matches!(s, Step::CopyFile { source, path }
+            if source.as_os_str() == "Cum.toml" && path == &staged.join("Cum.toml"))));
+        assert!(steps.iter().any(|s| matches!(s, Step::CopyFile { path, .. }
+            if path == &staged.join("bin/program"))));
+        let [.., Step::Tarball { source, output }, Step::Checksum { .. }] = steps.as_slice() else {
+            panic!("Package is not packed and checksummed last");
+        };
+        assert_eq!(source, &staged);
+        assert_eq!(output.as_os_str(), "target/package/program-1.0.0.tar.gz");
+    }
+
+    #[test]
+    fn simple_planner_compdb_lists_every_source() {
+        let _dir = set_dir_to_tests();
+        let fs_m = FilesystemManagerCell::default();
+        let mut mock_ctx =
+            MockFactory::mock_ctx_for_call(&["cum.exe", "compdb", "--preset=release"]);
+
+        let mut planner = Planner::new(&mut mock_ctx, fs_m, DiagnosticsCell::default());
+        planner.try_make_plan().unwrap();
+
+        let [Step::WriteFile { path, content }] = mock_ctx.plan.steps().as_slice() else {
+            panic!("Compilation database is not the only planned step");
+        };
+        assert_eq!(path.as_os_str(), super::COMPDB_PATH);
+        assert!(content.contains("\"file\": \"src/dep1.cpp\""));
+        assert!(content.contains("\"file\": \"src/main.cpp\""));
+        assert!(content.contains("\"arguments\": [\"clang++\", \"-std=c++20\""));
+        assert!(content.contains("\"-DCUM_RELEASE\""));
+    }
+
+    #[test]
+    fn simple_planner_compdb_is_written_before_run() {
+        let _dir = set_dir_to_tests();
+        let written = |mock_ctx: &Context| {
+            mock_ctx.plan.steps().iter().position(|s| {
+                matches!(s, Step::WriteFile { path, .. }
+                    if path.as_os_str() == super::COMPDB_PATH)
+            })
+        };
+
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "run", "--force"]);
+        let mut planner = Planner::new(
+            &mut mock_ctx,
+            FilesystemManagerCell::default(),
+            DiagnosticsCell::default(),
+        );
+        planner.try_make_plan().unwrap();
+        let run = mock_ctx
+            .plan
+            .steps()
+            .iter()
+            .position(|s| matches!(s, Step::Run { .. }));
+        assert!(written(&mock_ctx).is_some_and(|w| Some(w) < run));
+
+        // Tests are compiled too, database follows them.
+        let mut mock_ctx = MockFactory::mock_ctx_for_call(&["cum.exe", "test", "--force"]);
+        let mut planner = Planner::new(
+            &mut mock_ctx,
+            FilesystemManagerCell::default(),
+            DiagnosticsCell::default(),
+        );
+        planner.try_make_plan().unwrap();
+        let run = mock_ctx
+            .plan
+            .steps()
+            .iter()
+            .position(|s| matches!(s, Step::Run { .. }));
+        assert!(written(&mock_ctx).is_some_and(|w| Some(w) < run));
+    }
 }
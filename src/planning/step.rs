@@ -8,15 +8,36 @@ pub enum Step {
         source: PathBuf,
         output: PathBuf,
         preset: String,
+        target: String,
+    },
+    ModuleInterface {
+        source: PathBuf,
+        output: PathBuf,
+        preset: String,
+        target: String,
+    },
+    PrecompiledHeader {
+        header: PathBuf,
+        output: PathBuf,
+        preset: String,
+        target: String,
     },
     Linkage {
         source: Vec<PathBuf>,
         output: PathBuf,
         preset: String,
+        target: String,
+    },
+    Archive {
+        source: Vec<PathBuf>,
+        output: PathBuf,
+        target: String,
     },
     Run {
         exe: String,
         args: Vec<String>,
+        /// Directories searched for shared libraries.
+        lib_dirs: Vec<PathBuf>,
     },
     CreateDir {
         path: PathBuf,
@@ -30,18 +51,50 @@ pub enum Step {
     RemoveFile {
         path: PathBuf,
     },
+    /// Original is relative to directory of link.
+    CreateSymlink {
+        path: PathBuf,
+        original: PathBuf,
+    },
+    /// Existing file is replaced.
+    CopyFile {
+        source: PathBuf,
+        path: PathBuf,
+    },
+    WriteFile {
+        path: PathBuf,
+        content: String,
+    },
+    /// Gzipped tarball of directory, independent of timestamps and owners.
+    Tarball {
+        source: PathBuf,
+        output: PathBuf,
+    },
+    /// Sha256 of file, in format of 'sha256sum'.
+    Checksum {
+        source: PathBuf,
+        output: PathBuf,
+    },
 }
 
 impl Step {
     pub fn accept<V: PlanVisitor>(&self, visitor: &V) {
         match self {
             Step::Compilation { .. } => visitor.visit_compilation(self),
+            Step::ModuleInterface { .. } => visitor.visit_module_interface(self),
+            Step::PrecompiledHeader { .. } => visitor.visit_precompiled_header(self),
             Step::Linkage { .. } => visitor.visit_linkage(self),
+            Step::Archive { .. } => visitor.visit_archive(self),
             Step::Run { .. } => visitor.visit_run(self),
             Step::CreateDir { .. } => visitor.visit_make_dir(self),
             Step::RemoveDir { .. } => visitor.visit_remove_dir(self),
             Step::CreateFile { .. } => visitor.visit_make_file(self),
             Step::RemoveFile { .. } => visitor.visit_remove_file(self),
+            Step::CreateSymlink { .. } => visitor.visit_make_symlink(self),
+            Step::CopyFile { .. } => visitor.visit_copy_file(self),
+            Step::WriteFile { .. } => visitor.visit_write_file(self),
+            Step::Tarball { .. } => visitor.visit_tarball(self),
+            Step::Checksum { .. } => visitor.visit_checksum(self),
         }
     }
 }
@@ -1,6 +1,7 @@
 use std::{
     env::{self},
     path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
 };
 
 use crate::{
@@ -37,15 +38,19 @@ impl MockFactory {
     }
 
     pub fn mock_ctx_for_call(args: &[&str]) -> Context {
-        let mut ctx = Context::default();
-        ctx.config = Self::mock_cfg_default();
-        ctx.args = Self::mock_args(args);
-        ctx
+        Context {
+            config: Self::mock_cfg_default(),
+            args: Self::mock_args(args),
+            ..Default::default()
+        }
     }
 }
 
-#[inline]
-pub fn set_dir_to_tests() {
+/// Held by tests depending on current directory, as some of them change it.
+static CURRENT_DIR: Mutex<()> = Mutex::new(());
+
+pub fn set_dir_to_tests() -> MutexGuard<'static, ()> {
+    let guard = CURRENT_DIR.lock().unwrap_or_else(PoisonError::into_inner);
     if env::current_dir()
         .unwrap()
         .file_name()
@@ -56,4 +61,5 @@ pub fn set_dir_to_tests() {
     {
         env::set_current_dir(Path::new("test_assets/")).unwrap();
     }
+    guard
 }